@@ -0,0 +1,696 @@
+use std::{collections::VecDeque, path::PathBuf, sync::Arc, thread, time::{Duration, Instant}};
+
+use ahash::{HashMap, HashSet};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Serialize, Deserialize};
+use strum::{EnumIter, IntoEnumIterator};
+
+use library::{BytesSize, ContentSource, EntryId, FileEntry, FileEntryKind, LibraryError, MusicFileEntry, MusicLibrary, SfxFileEntry, SfxLibrary};
+use library::music::Song;
+use library::sfx::{EntryKind, SfxLibraryEntry};
+
+use crate::localized_enum;
+
+use super::{AppState, LibraryPage};
+use super::search::ListedMode;
+use super::tools::{ItemResult, ReportItem, ToolReport};
+
+static DOWNLOAD_QUEUE_FILE: Lazy<PathBuf> = Lazy::new(|| {
+    files::paths::PROJECT_DIR.config_local_dir().join("download_queue.json")
+});
+
+/// Delay before the first retry of a download, doubled for every further retry.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+/// How long idle workers wait before checking again whether a retry is due.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// The queue is saved at most this often while it changes.
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+localized_enum! {
+    #[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash, EnumIter)]
+    pub enum DownloadStatus = "downloads.status" {
+        #[default]
+        Queued = "queued",
+        Downloading = "downloading",
+        Failed = "failed",
+        Done = "done",
+        /// The file was already in the GD folder.
+        Skipped = "skipped",
+    }
+}
+
+impl DownloadStatus {
+    /// Whether the download won't be attempted again unless it's queued again.
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Failed | Self::Done | Self::Skipped)
+    }
+
+    fn is_successful(self) -> bool {
+        matches!(self, Self::Done | Self::Skipped)
+    }
+}
+
+type DownloadKey = (FileEntryKind, EntryId);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueuedDownload {
+    pub kind: FileEntryKind,
+    pub id: EntryId,
+    /// See [`FileEntry::expected_size`].
+    pub expected_size: Option<BytesSize>,
+    pub status: DownloadStatus,
+    /// Number of failed attempts so far.
+    #[serde(default)]
+    pub attempts: u32,
+    /// Error of the last failed attempt.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Queued without knowing whether the ID exists, e.g. from an ID range.
+    /// Removed from the queue instead of failing if the server doesn't have it.
+    #[serde(default)]
+    pub may_not_exist: bool,
+    #[serde(skip)]
+    retry_at: Option<Instant>,
+}
+
+impl QueuedDownload {
+    fn new(file_entry: &impl FileEntry, may_not_exist: bool) -> Self {
+        Self {
+            kind: file_entry.kind(),
+            id: file_entry.id(),
+            expected_size: file_entry.expected_size(),
+            status: DownloadStatus::Queued,
+            attempts: 0,
+            error: None,
+            may_not_exist,
+            retry_at: None,
+        }
+    }
+
+    fn key(&self) -> DownloadKey {
+        (self.kind, self.id)
+    }
+
+    fn requeue(&mut self) {
+        self.status = DownloadStatus::Queued;
+        self.attempts = 0;
+        self.error = None;
+        self.retry_at = None;
+    }
+}
+
+/// What's stored in [`DOWNLOAD_QUEUE_FILE`]. Finished downloads are left out.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct SavedQueue {
+    paused: bool,
+    items: Vec<QueuedDownload>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadCounts {
+    pub queued: usize,
+    pub downloading: usize,
+    pub failed: usize,
+    pub done: usize,
+    pub skipped: usize,
+}
+
+impl DownloadCounts {
+    pub fn total(&self) -> usize {
+        self.queued + self.downloading + self.failed + self.done + self.skipped
+    }
+
+    /// Downloads which won't be attempted again, see [`DownloadStatus::is_finished`].
+    pub fn finished(&self) -> usize {
+        DownloadStatus::iter()
+            .filter(|status| status.is_finished())
+            .map(|status| self.get(status))
+            .sum()
+    }
+
+    pub fn get(&self, status: DownloadStatus) -> usize {
+        match status {
+            DownloadStatus::Queued => self.queued,
+            DownloadStatus::Downloading => self.downloading,
+            DownloadStatus::Failed => self.failed,
+            DownloadStatus::Done => self.done,
+            DownloadStatus::Skipped => self.skipped,
+        }
+    }
+}
+
+type DownloadedIds = Arc<Mutex<HashSet<EntryId>>>;
+type Cache = Arc<Mutex<HashMap<EntryId, Vec<u8>>>>;
+
+/// Everything a worker needs to download files, captured when the downloads are started.
+pub struct DownloadContext {
+    gd_folder: String,
+    content_source: Arc<dyn ContentSource>,
+    sfx: (DownloadedIds, Cache),
+    music: (DownloadedIds, Cache),
+    /// Attempts per download, including the first one.
+    max_attempts: u32,
+}
+
+impl DownloadContext {
+    /// Returns whether the file had to be downloaded.
+    fn download(&self, download: &QueuedDownload, queue: &DownloadQueue) -> Result<bool, LibraryError> {
+        match download.kind {
+            FileEntryKind::Sound => self.download_file(SfxFileEntry::with_expected_size(download.id, download.expected_size), queue),
+            FileEntryKind::Song => self.download_file(MusicFileEntry::with_expected_size(download.id, download.expected_size), queue),
+        }
+    }
+
+    fn download_file(&self, file_entry: impl FileEntry, queue: &DownloadQueue) -> Result<bool, LibraryError> {
+        let (downloaded, cache) = match file_entry.kind() {
+            FileEntryKind::Sound => &self.sfx,
+            FileEntryKind::Song => &self.music,
+        };
+
+        let is_missing = !file_entry.file_exists(&self.gd_folder);
+        if is_missing {
+            let cached = cache.lock().get(&file_entry.id()).cloned();
+            let bytes = match cached {
+                Some(bytes) => bytes,
+                None => file_entry.try_download_bytes(&*self.content_source)?,
+            };
+
+            // don't write anything after the download was cancelled
+            if !queue.is_downloading((file_entry.kind(), file_entry.id())) {
+                return Ok(true)
+            }
+
+            file_entry.try_write_bytes(&self.gd_folder, bytes)?;
+        }
+
+        downloaded.lock().insert(file_entry.id());
+        Ok(is_missing)
+    }
+}
+
+enum NextDownload {
+    Download(QueuedDownload),
+    /// Only retries which aren't due yet are left.
+    Wait,
+    Stop,
+}
+
+#[derive(Default)]
+struct QueueState {
+    items: Vec<QueuedDownload>,
+    positions: HashMap<DownloadKey, usize>,
+    /// Queued downloads in the order they should run.
+    pending: VecDeque<DownloadKey>,
+    paused: bool,
+    workers: usize,
+    changed: bool,
+    last_save: Option<Instant>,
+}
+
+impl QueueState {
+    fn get_mut(&mut self, key: DownloadKey) -> Option<&mut QueuedDownload> {
+        self.positions.get(&key).map(|&position| &mut self.items[position])
+    }
+
+    /// Removes a single item which isn't pending, without going through the whole queue.
+    /// The last item takes its place.
+    fn remove(&mut self, key: DownloadKey) {
+        let Some(position) = self.positions.remove(&key) else { return };
+        self.items.swap_remove(position);
+        if let Some(moved) = self.items.get(position) {
+            self.positions.insert(moved.key(), position);
+        }
+        self.changed = true;
+    }
+
+    /// Removes the items for which `keep` returns false, keeping the order of the rest.
+    fn retain(&mut self, keep: impl Fn(&QueuedDownload) -> bool) {
+        self.items.retain(keep);
+        self.positions = self.items.iter().enumerate().map(|(position, item)| (item.key(), position)).collect();
+        let positions = &self.positions;
+        self.pending.retain(|key| positions.contains_key(key));
+        self.changed = true;
+    }
+}
+
+/// Downloads files in the background with a limited number of workers,
+/// retrying transient failures with exponential backoff.
+/// Unfinished downloads are saved and continue after a restart.
+#[derive(Clone, Default)]
+pub struct DownloadQueue(Arc<Mutex<QueueState>>);
+
+impl DownloadQueue {
+    pub fn load() -> Self {
+        let saved: SavedQueue = files::read_json(&*DOWNLOAD_QUEUE_FILE).unwrap_or_default();
+
+        let queue = Self::default();
+        {
+            let mut state = queue.0.lock();
+            state.paused = saved.paused;
+            for mut item in saved.items {
+                if item.status == DownloadStatus::Downloading {
+                    item.status = DownloadStatus::Queued;
+                }
+                if state.positions.contains_key(&item.key()) { continue }
+
+                if item.status == DownloadStatus::Queued {
+                    state.pending.push_back(item.key());
+                }
+                let position = state.items.len();
+                state.positions.insert(item.key(), position);
+                state.items.push(item);
+            }
+        }
+        queue
+    }
+
+    fn try_save(state: &mut QueueState) -> Result<()> {
+        let saved = SavedQueue {
+            paused: state.paused,
+            items: state.items.iter()
+                .filter(|item| !item.status.is_successful())
+                .cloned()
+                .collect(),
+        };
+        let json_data = serde_json::to_string(&saved).expect("derived serialization shouldn't fail");
+
+        state.changed = false;
+        state.last_save = Some(Instant::now());
+
+        let _ = files::create_parent_dirs(&*DOWNLOAD_QUEUE_FILE);
+        files::write_file(&*DOWNLOAD_QUEUE_FILE, json_data)
+    }
+
+    /// Saves the queue if it changed and wasn't saved too recently.
+    pub fn try_save_if_changed(&self) {
+        let mut state = self.0.lock();
+        if !state.changed { return }
+        if state.last_save.is_some_and(|last_save| last_save.elapsed() < SAVE_INTERVAL) { return }
+
+        let _ = Self::try_save(&mut state);
+    }
+
+    /// Adds downloads to the end of the queue. Finished downloads are queued again,
+    /// downloads which are already queued are left as they are.
+    /// Returns the number of newly queued downloads.
+    pub fn enqueue(&self, files: impl IntoIterator<Item = impl FileEntry>) -> usize {
+        self.enqueue_downloads(files, false)
+    }
+
+    /// Like [`Self::enqueue`], but files which the server doesn't have are dropped from the queue.
+    pub fn enqueue_unknown(&self, files: impl IntoIterator<Item = impl FileEntry>) -> usize {
+        self.enqueue_downloads(files, true)
+    }
+
+    fn enqueue_downloads(&self, files: impl IntoIterator<Item = impl FileEntry>, may_not_exist: bool) -> usize {
+        let mut state = self.0.lock();
+        let mut queued = 0;
+
+        for file_entry in files {
+            let download = QueuedDownload::new(&file_entry, may_not_exist);
+            let key = download.key();
+
+            match state.get_mut(key) {
+                Some(existing) if !existing.status.is_finished() => continue,
+                Some(existing) => {
+                    existing.requeue();
+                    existing.may_not_exist = may_not_exist;
+                }
+                None => {
+                    let position = state.items.len();
+                    state.positions.insert(key, position);
+                    state.items.push(download);
+                }
+            }
+            state.pending.push_back(key);
+            queued += 1;
+        }
+
+        state.changed |= queued > 0;
+        queued
+    }
+
+    /// Starts workers until `concurrency` are running, unless the queue is paused.
+    pub fn start(&self, context: DownloadContext, concurrency: usize) {
+        let context = Arc::new(context);
+        let mut state = self.0.lock();
+        if state.paused { return }
+
+        while state.workers < concurrency.max(1) && state.workers < state.pending.len() {
+            state.workers += 1;
+
+            let queue = self.clone();
+            let context = Arc::clone(&context);
+            thread::spawn(move || queue.run_worker(&context));
+        }
+    }
+
+    fn run_worker(&self, context: &DownloadContext) {
+        loop {
+            let download = match self.next_download() {
+                NextDownload::Download(download) => download,
+                NextDownload::Wait => {
+                    thread::sleep(POLL_INTERVAL);
+                    continue
+                }
+                NextDownload::Stop => break,
+            };
+
+            let result = context.download(&download, self);
+            self.finish(download.key(), result, context.max_attempts);
+        }
+    }
+
+    fn next_download(&self) -> NextDownload {
+        let mut state = self.0.lock();
+
+        if state.paused || state.pending.is_empty() {
+            state.workers -= 1;
+            // save right away once everything is done
+            if state.workers == 0 {
+                let _ = Self::try_save(&mut state);
+            }
+            return NextDownload::Stop
+        }
+
+        let now = Instant::now();
+        let state = &mut *state;
+        let ready = state.pending.iter().position(|key| {
+            state.positions.get(key)
+                .and_then(|&position| state.items[position].retry_at)
+                .is_none_or(|retry_at| retry_at <= now)
+        });
+
+        let Some(index) = ready else { return NextDownload::Wait };
+        let key = state.pending.remove(index).expect("index should be in range");
+
+        let download = state.get_mut(key).expect("pending downloads should be in the queue");
+        download.status = DownloadStatus::Downloading;
+        let download = download.clone();
+
+        state.changed = true;
+        NextDownload::Download(download)
+    }
+
+    fn finish(&self, key: DownloadKey, result: Result<bool, LibraryError>, max_attempts: u32) {
+        let mut state = self.0.lock();
+        let Some(download) = state.get_mut(key) else { return };
+        // cancelled in the meantime
+        if download.status != DownloadStatus::Downloading { return }
+
+        match result {
+            Ok(downloaded) => {
+                download.status = if downloaded { DownloadStatus::Done } else { DownloadStatus::Skipped };
+                download.error = None;
+            }
+            Err(error) if download.may_not_exist && error.is_not_found() => {
+                // running downloads aren't pending
+                state.remove(key);
+                return
+            }
+            Err(error) => {
+                let is_transient = error.is_transient();
+                download.attempts += 1;
+                download.error = Some(format!("{:#}", anyhow::Error::new(error)));
+
+                if is_transient && download.attempts < max_attempts {
+                    let delay = RETRY_DELAY.saturating_mul(1 << (download.attempts - 1).min(16)).min(MAX_RETRY_DELAY);
+                    download.status = DownloadStatus::Queued;
+                    download.retry_at = Some(Instant::now() + delay);
+                    state.pending.push_back(key);
+                } else {
+                    download.status = DownloadStatus::Failed;
+                }
+            }
+        }
+        state.changed = true;
+    }
+
+    fn is_downloading(&self, key: DownloadKey) -> bool {
+        let state = self.0.lock();
+        state.positions.get(&key).is_some_and(|&position| state.items[position].status == DownloadStatus::Downloading)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.lock().paused
+    }
+
+    /// Running workers finish their current download before stopping.
+    pub fn set_paused(&self, paused: bool) {
+        let mut state = self.0.lock();
+        state.paused = paused;
+        state.changed = true;
+    }
+
+    /// Whether downloads are running, including workers finishing their last download after pausing.
+    pub fn is_active(&self) -> bool {
+        self.0.lock().workers > 0
+    }
+
+    /// Whether there are queued downloads, but nothing is downloading them, e.g. after a restart.
+    pub fn is_stalled(&self) -> bool {
+        let state = self.0.lock();
+        !state.paused && state.workers == 0 && !state.pending.is_empty()
+    }
+
+    /// Removes all queued and running downloads. Running downloads aren't written to the GD folder.
+    pub fn cancel(&self) {
+        self.0.lock().retain(|item| item.status.is_finished());
+    }
+
+    /// Queues all failed downloads again. Returns the number of queued downloads.
+    pub fn retry_failed(&self) -> usize {
+        let mut state = self.0.lock();
+        let state = &mut *state;
+        let mut queued = 0;
+
+        for item in &mut state.items {
+            if item.status != DownloadStatus::Failed { continue }
+            item.requeue();
+            state.pending.push_back(item.key());
+            queued += 1;
+        }
+
+        state.changed |= queued > 0;
+        queued
+    }
+
+    pub fn clear_finished(&self) {
+        self.0.lock().retain(|item| !item.status.is_successful());
+    }
+
+    pub fn counts(&self) -> DownloadCounts {
+        let state = self.0.lock();
+        let mut counts = DownloadCounts::default();
+        for item in &state.items {
+            *match item.status {
+                DownloadStatus::Queued => &mut counts.queued,
+                DownloadStatus::Downloading => &mut counts.downloading,
+                DownloadStatus::Failed => &mut counts.failed,
+                DownloadStatus::Done => &mut counts.done,
+                DownloadStatus::Skipped => &mut counts.skipped,
+            } += 1;
+        }
+        counts
+    }
+
+    /// Report of the finished downloads, in queue order.
+    pub fn report(&self) -> ToolReport {
+        let items = self.0.lock().items.iter()
+            .filter_map(|item| {
+                let result = match item.status {
+                    DownloadStatus::Done => ItemResult::Succeeded,
+                    DownloadStatus::Skipped => ItemResult::Skipped,
+                    DownloadStatus::Failed => ItemResult::Failed,
+                    DownloadStatus::Queued | DownloadStatus::Downloading => return None,
+                };
+                Some(ReportItem { kind: item.kind, id: item.id, result, error: item.error.clone() })
+            })
+            .collect();
+
+        ToolReport { translation_key: "tools.downloads".to_string(), items }
+    }
+
+    /// The downloads with `status`, in queue order.
+    pub fn items(&self, status: DownloadStatus) -> Vec<QueuedDownload> {
+        self.0.lock().items.iter()
+            .filter(|item| item.status == status)
+            .cloned()
+            .collect()
+    }
+}
+
+impl AppState {
+    /// Adds downloads to the download queue and starts it.
+    pub fn queue_downloads(&self, files: impl IntoIterator<Item = impl FileEntry>) {
+        if !self.is_gd_folder_valid() || self.is_offline() { return }
+
+        if self.download_queue.enqueue(files) > 0 {
+            self.start_downloads();
+        }
+    }
+
+    /// Like [`Self::queue_downloads`] for files which might not exist, e.g. an ID range.
+    /// Files which the server doesn't have are left out instead of failing.
+    pub fn queue_range_downloads(&self, files: impl IntoIterator<Item = impl FileEntry>) {
+        if !self.is_gd_folder_valid() || self.is_offline() { return }
+
+        if self.download_queue.enqueue_unknown(files) > 0 {
+            self.start_downloads();
+        }
+    }
+
+    /// Starts workers for the queued downloads, e.g. after resuming or restarting the app.
+    pub fn start_downloads(&self) {
+        if !self.is_gd_folder_valid() || self.is_offline() { return }
+
+        let context = DownloadContext {
+            gd_folder: self.settings.gd_folder.clone(),
+            content_source: Arc::clone(&self.content_source),
+            sfx: (Arc::clone(&self.downloaded_sfx), Arc::clone(&self.sfx_cache)),
+            music: (Arc::clone(&self.downloaded_music), Arc::clone(&self.music_cache)),
+            max_attempts: self.settings.download_retries + 1,
+        };
+        self.download_queue.start(context, self.settings.download_concurrency);
+    }
+
+    /// Queues every sound within `category`, including nested categories.
+    pub fn download_category(&self, library: &SfxLibrary, category: &SfxLibraryEntry) {
+        self.queue_downloads(
+            library.iter_depth_first(category)
+                .filter(|entry| matches!(entry.kind, EntryKind::Sound { .. }))
+                .map(SfxLibraryEntry::into_file_entry)
+        );
+    }
+
+    /// Queues every sound or song shown on the current library page which matches the search and filters.
+    pub fn download_search_results(&self, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
+        match self.library_page {
+            LibraryPage::Sfx => self.queue_downloads(
+                sfx_library.iter_sounds()
+                    .filter(|entry| self.is_matching_sound(entry, sfx_library))
+                    .map(SfxLibraryEntry::into_file_entry)
+            ),
+            LibraryPage::Music => match self.music_filters.listed_mode {
+                ListedMode::Listed => self.queue_downloads(
+                    music_library.songs.values()
+                        .filter(|song| self.music_filters.matches(song) && self.is_matching_song(song, music_library))
+                        .map(Song::into_file_entry)
+                ),
+                ListedMode::Unlisted => self.queue_downloads(
+                    self.unlisted_songs()
+                        .filter(|song| self.is_matching_song(song, music_library))
+                        .map(|song| song.into_file_entry())
+                ),
+            },
+        }
+    }
+
+    pub fn retry_failed_downloads(&self) {
+        if self.download_queue.retry_failed() > 0 {
+            self.start_downloads();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn failure(status: u16) -> LibraryError {
+        LibraryError::HttpStatus { url: String::new(), status }
+    }
+
+    fn next(queue: &DownloadQueue) -> QueuedDownload {
+        match queue.next_download() {
+            NextDownload::Download(download) => download,
+            _ => panic!("expected a download"),
+        }
+    }
+
+    #[test]
+    fn test_queue() {
+        let queue = DownloadQueue::default();
+        queue.0.lock().workers = 1;
+
+        assert_eq!(queue.enqueue([SfxFileEntry::new(1), SfxFileEntry::new(2)]), 2);
+        // already queued
+        assert_eq!(queue.enqueue([SfxFileEntry::new(1)]), 0);
+
+        let first = next(&queue);
+        assert_eq!(first.id, 1);
+        assert!(queue.is_downloading(first.key()));
+
+        // server errors are retried later, missing files aren't
+        queue.finish(first.key(), Err(failure(503)), 3);
+        let second = next(&queue);
+        assert_eq!(second.id, 2);
+        queue.finish(second.key(), Err(failure(404)), 3);
+
+        assert_eq!(queue.counts(), DownloadCounts { queued: 1, failed: 1, ..Default::default() });
+        assert!(matches!(queue.next_download(), NextDownload::Wait));
+
+        queue.0.lock().get_mut(first.key()).unwrap().retry_at = None;
+        let retried = next(&queue);
+        assert_eq!((retried.id, retried.attempts), (1, 1));
+
+        // cancelled downloads are dropped when they finish
+        queue.cancel();
+        queue.finish(retried.key(), Ok(true), 3);
+        assert_eq!(queue.counts(), DownloadCounts { failed: 1, ..Default::default() });
+
+        assert_eq!(queue.retry_failed(), 1);
+        let retried = next(&queue);
+        queue.finish(retried.key(), Ok(true), 3);
+        assert_eq!(queue.counts(), DownloadCounts { done: 1, ..Default::default() });
+
+        assert_eq!(queue.enqueue([SfxFileEntry::new(3)]), 1);
+        let present = next(&queue);
+        queue.finish(present.key(), Ok(false), 3);
+        assert_eq!(queue.counts(), DownloadCounts { done: 1, skipped: 1, ..Default::default() });
+
+        queue.clear_finished();
+        assert_eq!(queue.counts().total(), 0);
+    }
+
+    #[test]
+    fn test_counts() {
+        let counts = DownloadCounts { queued: 1, downloading: 2, failed: 3, done: 4, skipped: 5 };
+        assert_eq!(counts.total(), 15);
+        assert_eq!(counts.finished(), 12);
+
+        // files which were already present count as finished too
+        let counts = DownloadCounts { skipped: 3, ..Default::default() };
+        assert_eq!(counts.finished(), counts.total());
+    }
+
+    #[test]
+    fn test_unknown_ids() {
+        let queue = DownloadQueue::default();
+        queue.0.lock().workers = 1;
+
+        assert_eq!(queue.enqueue_unknown([SfxFileEntry::new(1), SfxFileEntry::new(2)]), 2);
+
+        // IDs without a file are dropped, other errors still fail
+        let missing = next(&queue);
+        queue.finish(missing.key(), Err(failure(404)), 1);
+        let failed = next(&queue);
+        queue.finish(failed.key(), Err(failure(403)), 1);
+
+        assert_eq!(queue.counts(), DownloadCounts { failed: 1, ..Default::default() });
+        assert_eq!(queue.items(DownloadStatus::Failed)[0].id, 2);
+
+        // the item which takes the place of a dropped one can still be found
+        queue.enqueue_unknown([SfxFileEntry::new(3), SfxFileEntry::new(4)]);
+        let missing = next(&queue);
+        queue.finish(missing.key(), Err(failure(404)), 1);
+        let last = next(&queue);
+        assert_eq!(last.id, 4);
+        queue.finish(last.key(), Ok(true), 1);
+        assert_eq!(queue.counts(), DownloadCounts { failed: 1, done: 1, ..Default::default() });
+    }
+}
@@ -1,28 +1,146 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use ahash::HashSet;
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
 use once_cell::sync::Lazy;
 use serde::{Serialize, Deserialize};
+use strum::EnumIter;
 
-use library::EntryId;
+use library::{EntryId, FileEntry, FileEntryKind, MusicLibrary, SfxLibrary};
+
+use crate::localized_enum;
 
 static FAVORITES_FILE: Lazy<PathBuf> = Lazy::new(|| {
     files::paths::PROJECT_DIR.config_local_dir().join("favorites.json")
 });
 
+/// Holds the entries favorited with the star button. It can't be removed.
+pub const DEFAULT_COLLECTION: usize = 0;
+
+/// Songs of the music library start at this ID, lower IDs are sounds or Newgrounds songs.
+const FIRST_LIBRARY_SONG_ID: EntryId = 10000000;
+
+/// Identifies a sound or a song, since their IDs can overlap.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FavoriteKey {
+    pub kind: FileEntryKind,
+    pub id: EntryId,
+}
+
+impl FavoriteKey {
+    pub fn sfx(id: EntryId) -> Self {
+        Self { kind: FileEntryKind::Sound, id }
+    }
+
+    pub fn song(id: EntryId) -> Self {
+        Self { kind: FileEntryKind::Song, id }
+    }
+
+    pub fn of(file_entry: &impl FileEntry) -> Self {
+        Self { kind: file_entry.kind(), id: file_entry.id() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FavoriteItem {
+    pub key: FavoriteKey,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+/// A named list of sounds and songs, kept in the order they were added or arranged in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FavoriteCollection {
+    pub name: String,
+    pub items: Vec<FavoriteItem>,
+}
+
+impl FavoriteCollection {
+    pub fn contains(&self, key: FavoriteKey) -> bool {
+        self.position(key).is_some()
+    }
+
+    fn position(&self, key: FavoriteKey) -> Option<usize> {
+        self.items.iter().position(|item| item.key == key)
+    }
+
+    /// Swaps an item with the previous or next item of the same kind for which `is_shown` is true,
+    /// so that it moves by one place among the items the user sees.
+    fn move_item(&mut self, key: FavoriteKey, towards_start: bool, is_shown: impl Fn(FavoriteKey) -> bool) {
+        let Some(position) = self.position(key) else { return };
+        let is_neighbor = |item: &FavoriteItem| item.key.kind == key.kind && is_shown(item.key);
+
+        let other = match towards_start {
+            true => self.items[..position].iter().rposition(is_neighbor),
+            false => self.items[position + 1..].iter().position(is_neighbor).map(|offset| position + 1 + offset),
+        };
+        if let Some(other) = other {
+            self.items.swap(position, other);
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Favorites(HashSet<EntryId>);
+pub struct Favorites {
+    collections: Vec<FavoriteCollection>,
+}
+
+/// `favorites.json` before collections were added: a single set of IDs shared by sounds and songs.
+type LegacyFavorites = HashSet<EntryId>;
 
 impl Default for Favorites {
     fn default() -> Self {
-        Self([4451].into_iter().collect())
+        Self::with_default_collection(vec![FavoriteKey::sfx(4451)])
     }
 }
 
 impl Favorites {
-    pub fn load() -> Self {
-        files::read_json(&*FAVORITES_FILE).unwrap_or_default()
+    pub fn load(sfx_library: &SfxLibrary, music_library: &MusicLibrary) -> Self {
+        if let Ok(favorites) = files::read_json::<Self>(&*FAVORITES_FILE) {
+            if !favorites.collections.is_empty() {
+                return favorites
+            }
+        }
+
+        match files::read_json::<LegacyFavorites>(&*FAVORITES_FILE) {
+            Ok(ids) => {
+                let favorites = Self::migrate(ids, sfx_library, music_library);
+                let _ = favorites.try_save();
+                favorites
+            }
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn with_default_collection(keys: Vec<FavoriteKey>) -> Self {
+        Self {
+            collections: vec![FavoriteCollection {
+                name: String::from("Favorites"),
+                items: keys.into_iter()
+                    .map(|key| FavoriteItem { key, note: String::new() })
+                    .collect(),
+            }],
+        }
+    }
+
+    /// Looks up which library each legacy ID belongs to, keeping both if it's in both.
+    fn migrate(ids: LegacyFavorites, sfx_library: &SfxLibrary, music_library: &MusicLibrary) -> Self {
+        let keys = ids.into_iter()
+            .sorted()
+            .flat_map(|id| {
+                let mut is_sound = sfx_library.entries.contains_key(&id);
+                let mut is_song = music_library.songs.contains_key(&id);
+                if !is_sound && !is_song {
+                    is_song = id >= FIRST_LIBRARY_SONG_ID;
+                    is_sound = !is_song;
+                }
+                [is_sound.then(|| FavoriteKey::sfx(id)), is_song.then(|| FavoriteKey::song(id))]
+            })
+            .flatten()
+            .collect();
+
+        Self::with_default_collection(keys)
     }
 
     fn try_save(&self) -> Result<()> {
@@ -32,20 +150,408 @@ impl Favorites {
         files::write_file(&*FAVORITES_FILE, json_data)
     }
 
-    pub fn has_favorite(&self, id: EntryId) -> bool {
-        self.0.contains(&id)
+    /// Applies a change and saves it, undoing the change if saving fails.
+    fn update(&mut self, change: impl FnOnce(&mut Vec<FavoriteCollection>)) -> Result<()> {
+        let previous = self.collections.clone();
+        change(&mut self.collections);
+
+        self.try_save().inspect_err(|_| self.collections = previous)
+    }
+
+    /// Never empty, the first collection is the [`DEFAULT_COLLECTION`].
+    pub fn collections(&self) -> &[FavoriteCollection] {
+        &self.collections
+    }
+
+    pub fn has_favorite(&self, key: FavoriteKey) -> bool {
+        self.collections[DEFAULT_COLLECTION].contains(key)
     }
 
-    pub fn toggle_favorite(&mut self, id: EntryId) {
-        if !self.0.insert(id) {
-            self.0.remove(&id);
+    pub fn toggle_favorite(&mut self, key: FavoriteKey) {
+        self.toggle_in_collection(DEFAULT_COLLECTION, key);
+    }
+
+    pub fn toggle_in_collection(&mut self, collection: usize, key: FavoriteKey) {
+        let _ = self.update(|collections| {
+            let Some(collection) = collections.get_mut(collection) else { return };
+            match collection.position(key) {
+                Some(position) => { collection.items.remove(position); }
+                None => collection.items.push(FavoriteItem { key, note: String::new() }),
+            }
+        });
+    }
+
+    /// Returns the index of the new collection, or `None` if the name is empty or already taken.
+    pub fn add_collection(&mut self, name: &str) -> Option<usize> {
+        let name = name.trim();
+        if name.is_empty() || self.collections.iter().any(|collection| collection.name == name) {
+            return None
         }
 
-        if self.try_save().is_err() {
-            // undo on failure
-            if !self.0.remove(&id) {
-                self.0.insert(id);
+        let _ = self.update(|collections| collections.push(FavoriteCollection { name: name.to_string(), items: Vec::new() }));
+        self.collections.iter().position(|collection| collection.name == name)
+    }
+
+    pub fn remove_collection(&mut self, collection: usize) {
+        if collection == DEFAULT_COLLECTION || collection >= self.collections.len() { return }
+
+        let _ = self.update(|collections| { collections.remove(collection); });
+    }
+
+    /// Moves an item one place towards the start or end of the collection, skipping items which aren't shown.
+    pub fn move_item(&mut self, collection: usize, key: FavoriteKey, towards_start: bool, is_shown: impl Fn(FavoriteKey) -> bool) {
+        let _ = self.update(|collections| {
+            if let Some(collection) = collections.get_mut(collection) {
+                collection.move_item(key, towards_start, is_shown);
+            }
+        });
+    }
+
+    pub fn set_note(&mut self, collection: usize, key: FavoriteKey, note: String) {
+        let _ = self.update(|collections| {
+            let item = collections.get_mut(collection)
+                .and_then(|collection| collection.items.iter_mut().find(|item| item.key == key));
+            if let Some(item) = item {
+                item.note = note;
             }
+        });
+    }
+}
+
+/// A collection in the form that's exported, with names so the importing side can check that
+/// the IDs still refer to the same sounds and songs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SharedCollection {
+    pub name: String,
+    pub items: Vec<SharedItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SharedItem {
+    pub kind: FileEntryKind,
+    pub id: EntryId,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+localized_enum! {
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter)]
+    pub enum ImportMode = "favorites.import_mode" {
+        /// Adds the imported entries to the collections with the same name.
+        #[default]
+        Merge = "merge",
+        /// Removes all existing collections and favorites first.
+        Replace = "replace",
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ImportReport {
+    /// Number of entries which weren't already part of their collection.
+    pub imported: usize,
+    /// Entries which aren't part of the current libraries, these weren't imported.
+    pub missing: Vec<SharedItem>,
+    /// Imported entries whose name in the current library differs from the exported one, with the current name.
+    pub renamed: Vec<(SharedItem, String)>,
+}
+
+fn entry_name<'a>(key: FavoriteKey, sfx_library: &'a SfxLibrary, music_library: &'a MusicLibrary) -> Option<&'a str> {
+    match key.kind {
+        FileEntryKind::Sound => sfx_library.get_entry(key.id).map(|entry| entry.name.as_str()),
+        FileEntryKind::Song => music_library.songs.get(&key.id).map(|song| song.name.as_str()),
+    }
+}
+
+/// Text files list each collection as a `[name]` line followed by one `<kind>\t<id>\t<name>[\t<note>]` line per entry,
+/// every other file is JSON.
+fn is_text_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("txt"))
+}
+
+impl Favorites {
+    pub fn to_shared(&self, sfx_library: &SfxLibrary, music_library: &MusicLibrary) -> Vec<SharedCollection> {
+        self.collections.iter()
+            .map(|collection| SharedCollection {
+                name: collection.name.clone(),
+                items: collection.items.iter()
+                    .map(|item| SharedItem {
+                        kind: item.key.kind,
+                        id: item.key.id,
+                        name: entry_name(item.key, sfx_library, music_library).unwrap_or_default().to_string(),
+                        note: item.note.clone(),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub fn export(&self, path: &Path, sfx_library: &SfxLibrary, music_library: &MusicLibrary) -> Result<()> {
+        let collections = self.to_shared(sfx_library, music_library);
+        let contents = match is_text_file(path) {
+            true => write_shared_text(&collections),
+            false => serde_json::to_string_pretty(&collections).expect("derived serialization shouldn't fail"),
+        };
+        files::write_file(path, contents)
+    }
+
+    pub fn import(&mut self, path: &Path, mode: ImportMode, sfx_library: &SfxLibrary, music_library: &MusicLibrary) -> Result<ImportReport> {
+        let contents = String::from_utf8(files::read_file(path)?)
+            .with_context(|| format!("{} isn't valid UTF-8", path.display()))?;
+        let collections = match is_text_file(path) {
+            true => parse_shared_text(&contents)?,
+            false => serde_json::from_str(&contents)
+                .with_context(|| format!("Couldn't parse {}", path.display()))?,
+        };
+
+        let (collections, report) = self.merge_shared(collections, mode, sfx_library, music_library);
+        self.update(|current| *current = collections)?;
+        Ok(report)
+    }
+
+    /// Collections match by name, the default collection keeps its place even when replacing.
+    fn merge_shared(
+        &self,
+        shared_collections: Vec<SharedCollection>,
+        mode: ImportMode,
+        sfx_library: &SfxLibrary,
+        music_library: &MusicLibrary,
+    ) -> (Vec<FavoriteCollection>, ImportReport) {
+        let mut collections = match mode {
+            ImportMode::Merge => self.collections.clone(),
+            ImportMode::Replace => vec![FavoriteCollection {
+                name: self.collections[DEFAULT_COLLECTION].name.clone(),
+                items: Vec::new(),
+            }],
+        };
+        let mut report = ImportReport::default();
+
+        for shared_collection in shared_collections {
+            let index = match collections.iter().position(|collection| collection.name == shared_collection.name) {
+                Some(index) => index,
+                None => {
+                    collections.push(FavoriteCollection { name: shared_collection.name, items: Vec::new() });
+                    collections.len() - 1
+                }
+            };
+
+            for item in shared_collection.items {
+                let key = FavoriteKey { kind: item.kind, id: item.id };
+                let Some(name) = entry_name(key, sfx_library, music_library) else {
+                    report.missing.push(item);
+                    continue
+                };
+                if name != item.name {
+                    report.renamed.push((item.clone(), name.to_string()));
+                }
+
+                let collection = &mut collections[index];
+                if !collection.contains(key) {
+                    collection.items.push(FavoriteItem { key, note: item.note });
+                    report.imported += 1;
+                }
+            }
+        }
+
+        (collections, report)
+    }
+}
+
+fn kind_name(kind: FileEntryKind) -> &'static str {
+    match kind {
+        FileEntryKind::Sound => "sound",
+        FileEntryKind::Song => "song",
+    }
+}
+
+fn write_shared_text(collections: &[SharedCollection]) -> String {
+    // tabs and line breaks would end the field early
+    let clean = |text: &str| text.replace(['\t', '\n', '\r'], " ");
+
+    let mut text = String::new();
+    for collection in collections {
+        text += &format!("[{}]\n", clean(&collection.name));
+        for item in &collection.items {
+            text += &format!("{}\t{}\t{}", kind_name(item.kind), item.id, clean(&item.name));
+            if !item.note.is_empty() {
+                text += &format!("\t{}", clean(&item.note));
+            }
+            text += "\n";
+        }
+    }
+    text
+}
+
+fn parse_shared_text(text: &str) -> Result<Vec<SharedCollection>> {
+    let mut collections: Vec<SharedCollection> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        if line.trim().is_empty() { continue }
+
+        if let Some(name) = line.trim().strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            collections.push(SharedCollection { name: name.to_string(), items: Vec::new() });
+            continue
         }
+
+        let collection = collections.last_mut()
+            .ok_or_else(|| anyhow!("Line {line_number}: expected a [collection name] before the first entry"))?;
+
+        let mut fields = line.split('\t');
+        let kind = match fields.next() {
+            Some("sound") => FileEntryKind::Sound,
+            Some("song") => FileEntryKind::Song,
+            _ => return Err(anyhow!("Line {line_number}: expected \"sound\" or \"song\"")),
+        };
+        let id = fields.next()
+            .and_then(|id| id.trim().parse().ok())
+            .ok_or_else(|| anyhow!("Line {line_number}: expected an ID"))?;
+
+        collection.items.push(SharedItem {
+            kind,
+            id,
+            name: fields.next().unwrap_or_default().to_string(),
+            note: fields.next().unwrap_or_default().to_string(),
+        });
+    }
+
+    Ok(collections)
+}
+
+#[cfg(test)]
+mod test {
+    use library::music::Song;
+    use library::sfx::{EntryKind, SfxLibraryEntry};
+
+    use super::*;
+
+    fn libraries() -> (SfxLibrary, MusicLibrary) {
+        let mut sfx_library = SfxLibrary::default();
+        for (id, name) in [(4451, "Fire In The Hole"), (4454, "Boom")] {
+            let kind = EntryKind::Sound { bytes: 0, duration: Default::default() };
+            let entry = SfxLibraryEntry { id, name: name.to_string(), parent_id: 0, kind, extra_fields: Vec::new() };
+            sfx_library.entries.insert(id, entry);
+        }
+
+        let mut music_library = MusicLibrary::default();
+        music_library.songs.insert(10000001, Song { id: 10000001, name: String::from("Glorious Morning"), ..Default::default() });
+
+        (sfx_library, music_library)
+    }
+
+    fn shared_item(kind: FileEntryKind, id: EntryId, name: &str) -> SharedItem {
+        SharedItem { kind, id, name: name.to_string(), note: String::new() }
+    }
+
+    fn collection(name: &str, keys: &[FavoriteKey]) -> FavoriteCollection {
+        FavoriteCollection {
+            name: name.to_string(),
+            items: keys.iter().map(|&key| FavoriteItem { key, note: String::new() }).collect(),
+        }
+    }
+
+    #[test]
+    fn test_shared_text_round_trip() {
+        let collections = vec![
+            SharedCollection {
+                name: String::from("Favorites"),
+                items: vec![
+                    SharedItem { kind: FileEntryKind::Sound, id: 4451, name: String::from("Fire In The Hole"), note: String::new() },
+                    SharedItem { kind: FileEntryKind::Song, id: 10000001, name: String::from("Glorious Morning"), note: String::from("intro") },
+                ],
+            },
+            SharedCollection { name: String::from("Empty"), items: Vec::new() },
+        ];
+
+        let text = write_shared_text(&collections);
+        assert_eq!(text, "[Favorites]\nsound\t4451\tFire In The Hole\nsong\t10000001\tGlorious Morning\tintro\n[Empty]\n");
+        assert_eq!(parse_shared_text(&text).unwrap(), collections);
+
+        assert!(parse_shared_text("sound\t1\tBoom").is_err());
+        assert!(parse_shared_text("[A]\nsfx\t1\tBoom").is_err());
+    }
+
+    #[test]
+    fn test_move_item() {
+        let keys = |collection: &FavoriteCollection| collection.items.iter().map(|item| item.key).collect::<Vec<_>>();
+        let mut collection = FavoriteCollection {
+            name: String::from("Favorites"),
+            items: [FavoriteKey::sfx(1), FavoriteKey::song(2), FavoriteKey::sfx(3), FavoriteKey::sfx(4), FavoriteKey::sfx(5)]
+                .into_iter()
+                .map(|key| FavoriteItem { key, note: String::new() })
+                .collect(),
+        };
+        // 4 is filtered out by the search
+        let is_shown = |key: FavoriteKey| key != FavoriteKey::sfx(4);
+
+        // songs in between are skipped
+        collection.move_item(FavoriteKey::sfx(3), true, is_shown);
+        assert_eq!(keys(&collection), [FavoriteKey::sfx(3), FavoriteKey::song(2), FavoriteKey::sfx(1), FavoriteKey::sfx(4), FavoriteKey::sfx(5)]);
+
+        // and so are items which aren't shown
+        collection.move_item(FavoriteKey::sfx(1), false, is_shown);
+        assert_eq!(keys(&collection), [FavoriteKey::sfx(3), FavoriteKey::song(2), FavoriteKey::sfx(5), FavoriteKey::sfx(4), FavoriteKey::sfx(1)]);
+
+        // the last shown item stays in place
+        collection.move_item(FavoriteKey::sfx(1), false, is_shown);
+        collection.move_item(FavoriteKey::song(2), true, is_shown);
+        assert_eq!(keys(&collection), [FavoriteKey::sfx(3), FavoriteKey::song(2), FavoriteKey::sfx(5), FavoriteKey::sfx(4), FavoriteKey::sfx(1)]);
+    }
+
+    #[test]
+    fn test_merge_shared() {
+        let (sfx_library, music_library) = libraries();
+        let favorites = Favorites {
+            collections: vec![
+                collection("My favorites", &[FavoriteKey::sfx(4451)]),
+                collection("Loud", &[FavoriteKey::sfx(4454)]),
+            ],
+        };
+        let shared = vec![
+            SharedCollection {
+                name: String::from("Loud"),
+                items: vec![
+                    // already part of the collection
+                    shared_item(FileEntryKind::Sound, 4454, "Boom"),
+                    SharedItem { note: String::from("intro"), ..shared_item(FileEntryKind::Song, 10000001, "Glorious Morning") },
+                    shared_item(FileEntryKind::Sound, 4451, "Fire in the hole!"),
+                    shared_item(FileEntryKind::Sound, 1, "Removed sound"),
+                ],
+            },
+            SharedCollection { name: String::from("New"), items: vec![shared_item(FileEntryKind::Sound, 4451, "Fire In The Hole")] },
+        ];
+
+        let mut loud = collection("Loud", &[FavoriteKey::sfx(4454), FavoriteKey::song(10000001), FavoriteKey::sfx(4451)]);
+        loud.items[1].note = String::from("intro");
+
+        let (collections, report) = favorites.merge_shared(shared.clone(), ImportMode::Merge, &sfx_library, &music_library);
+        assert_eq!(collections, [
+            collection("My favorites", &[FavoriteKey::sfx(4451)]),
+            loud.clone(),
+            collection("New", &[FavoriteKey::sfx(4451)]),
+        ]);
+        assert_eq!(report, ImportReport {
+            imported: 3,
+            missing: vec![shared_item(FileEntryKind::Sound, 1, "Removed sound")],
+            renamed: vec![(shared_item(FileEntryKind::Sound, 4451, "Fire in the hole!"), String::from("Fire In The Hole"))],
+        });
+
+        // the default collection keeps its name, but loses its items
+        let (collections, report) = favorites.merge_shared(shared, ImportMode::Replace, &sfx_library, &music_library);
+        assert_eq!(collections, [
+            collection("My favorites", &[]),
+            loud,
+            collection("New", &[FavoriteKey::sfx(4451)]),
+        ]);
+        assert_eq!(report.imported, 4);
+
+        // entries listed twice are only added once
+        let shared = vec![SharedCollection {
+            name: String::from("My favorites"),
+            items: vec![shared_item(FileEntryKind::Sound, 4454, "Boom"), shared_item(FileEntryKind::Sound, 4454, "Boom")],
+        }];
+        let (collections, report) = favorites.merge_shared(shared, ImportMode::Replace, &sfx_library, &music_library);
+        assert_eq!(collections, [collection("My favorites", &[FavoriteKey::sfx(4454)])]);
+        assert_eq!(report, ImportReport { imported: 1, ..Default::default() });
     }
 }
@@ -0,0 +1,120 @@
+use std::thread::{self, JoinHandle};
+
+use library::{LibraryError, MusicLibrary, SfxLibrary};
+use library::diff::{MusicLibraryDiff, SfxLibraryDiff};
+
+use super::settings::PersistentSettings;
+
+pub struct Libraries {
+    pub sfx_library: Result<SfxLibrary, LibraryError>,
+    pub music_library: Result<MusicLibrary, LibraryError>,
+    pub updates: LibraryUpdates,
+}
+
+/// Changes made by library updates downloaded during startup.
+#[derive(Default)]
+pub struct LibraryUpdates {
+    pub sfx: Option<SfxLibraryDiff>,
+    pub music: Option<MusicLibraryDiff>,
+}
+
+impl LibraryUpdates {
+    pub fn is_empty(&self) -> bool {
+        self.sfx.is_none() && self.music.is_none()
+    }
+}
+
+impl Libraries {
+    /// Loads both libraries in parallel from the folder and source configured in `settings`.
+    pub fn load(settings: &PersistentSettings) -> Self {
+        let gd_folder = &settings.gd_folder;
+        let content_source = settings.create_content_source();
+
+        thread::scope(|scope| {
+            let sfx_library_handle = scope.spawn(|| SfxLibrary::load_with_previous(gd_folder, &*content_source));
+            let music_library_handle = scope.spawn(|| MusicLibrary::load_with_previous(gd_folder, &*content_source));
+
+            let mut updates = LibraryUpdates::default();
+
+            let sfx_library = sfx_library_handle.join().unwrap().map(|(library, previous)| {
+                updates.sfx = previous
+                    .map(|previous| SfxLibraryDiff::between(&previous, &library))
+                    .filter(|diff| !diff.is_empty());
+                library
+            });
+            let music_library = music_library_handle.join().unwrap().map(|(library, previous)| {
+                updates.music = previous
+                    .map(|previous| MusicLibraryDiff::between(&previous, &library))
+                    .filter(|diff| !diff.is_empty());
+                library
+            });
+
+            Self { sfx_library, music_library, updates }
+        })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.sfx_library.is_ok() && self.music_library.is_ok()
+    }
+}
+
+/// Startup state while at least one library couldn't be loaded.
+pub struct LoadFailure {
+    pub settings: PersistentSettings,
+    pub libraries: Libraries,
+    retry: Option<JoinHandle<Libraries>>,
+}
+
+impl LoadFailure {
+    pub fn new(settings: PersistentSettings, libraries: Libraries) -> Self {
+        Self { settings, libraries, retry: None }
+    }
+
+    /// Loads the libraries again in the background, e.g. after selecting a different GD folder.
+    pub fn retry(&mut self) {
+        if self.is_retrying() { return }
+
+        let settings = self.settings.clone();
+        self.retry = Some(thread::spawn(move || Libraries::load(&settings)));
+    }
+
+    pub fn is_retrying(&self) -> bool {
+        self.retry.is_some()
+    }
+
+    /// Picks up the result of a finished retry. Libraries that loaded before are kept if they fail this time.
+    pub fn poll_retry(&mut self) {
+        if !self.retry.as_ref().is_some_and(JoinHandle::is_finished) { return }
+
+        let Some(Ok(libraries)) = self.retry.take().map(JoinHandle::join) else { return };
+
+        if libraries.sfx_library.is_ok() || self.libraries.sfx_library.is_err() {
+            self.libraries.sfx_library = libraries.sfx_library;
+        }
+        if libraries.music_library.is_ok() || self.libraries.music_library.is_err() {
+            self.libraries.music_library = libraries.music_library;
+        }
+        // the library files were already updated by an earlier attempt
+        if libraries.updates.sfx.is_some() {
+            self.libraries.updates.sfx = libraries.updates.sfx;
+        }
+        if libraries.updates.music.is_some() {
+            self.libraries.updates.music = libraries.updates.music;
+        }
+    }
+
+    /// Whether the app can be started with at least one of the libraries.
+    pub fn can_continue(&self) -> bool {
+        !self.is_retrying() && (self.libraries.sfx_library.is_ok() || self.libraries.music_library.is_ok())
+    }
+
+    /// Replaces libraries that couldn't be loaded with empty ones.
+    pub fn into_parts(self) -> (PersistentSettings, SfxLibrary, MusicLibrary, LibraryUpdates) {
+        (
+            self.settings,
+            self.libraries.sfx_library.unwrap_or_default(),
+            self.libraries.music_library.unwrap_or_default(),
+            self.libraries.updates,
+        )
+    }
+}
@@ -3,28 +3,41 @@ use std::{thread, sync::Arc, path::Path};
 use ahash::{HashMap, HashSet};
 use eframe::egui::{self, Visuals};
 use educe::Educe;
-use itertools::{Either, Itertools};
 use parking_lot::{Mutex, RwLock};
+use serde::{Serialize, Deserialize};
 use strum::EnumIter;
 
 use audio::{AudioSettings, AudioSystem};
-use library::{music, EntryId, FileEntry, FileEntryKind, MusicLibrary, SfxLibrary};
+use library::{music, ContentSource, EntryId, FileEntry, FileEntryKind, HttpSource, MusicLibrary, SfxLibrary};
 use library::sfx::{EntryKind, SfxLibraryEntry};
+use library::search::SearchIndex;
+use library::validate::ValidationReport;
 
 use crate::layout;
 use crate::{tabs::Tab, localized_enum};
 
-use self::favorites::Favorites;
+use self::downloads::DownloadQueue;
+use self::favorites::{FavoriteKey, Favorites, ImportMode};
 use self::konami::Konami;
-use self::search::{MusicFilters, SearchSettings};
+use self::loading::LibraryUpdates;
+use self::search::{EntryState, MusicFilters, SearchResults, SearchSettings};
 use self::settings::{ColorTheme, PersistentSettings};
-use self::tools::ToolProgress;
+use self::smart_collections::SmartCollections;
+use self::tools::{ToolProgress, ToolReport, VerificationReport};
+use self::watcher::FolderWatcher;
 
+/// Parent or artist of entries which aren't part of the library.
+pub const UNLISTED_ID: EntryId = EntryId::MAX;
+
+pub mod downloads;
 pub mod favorites;
 pub mod settings;
+pub mod smart_collections;
 pub mod search;
 pub mod tools;
 pub mod konami;
+pub mod loading;
+pub mod watcher;
 
 #[derive(Educe)]
 #[educe(Default)]
@@ -36,8 +49,27 @@ pub struct AppState {
 
     pub settings: PersistentSettings,
     pub favorites: Favorites,
+    /// Index of the collection shown in the favorites tab.
+    pub favorites_collection: usize,
+    /// Name entered for adding a favorites collection.
+    pub favorites_collection_name: String,
+    /// Whether the favorites tab shows the order of the collection instead of the sort order.
+    #[educe(Default = true)]
+    pub favorites_collection_order: bool,
+    pub favorites_import_mode: ImportMode,
+    /// Note being edited in the favorites tab, only saved once editing is done.
+    pub favorite_note: Option<(FavoriteKey, String)>,
+    pub smart_collections: SmartCollections,
+    /// Name entered for saving the current search as a smart collection.
+    pub smart_collection_name: String,
+
+    #[educe(Default = Arc::new(HttpSource::official()))]
+    pub content_source: Arc<dyn ContentSource>,
     
     pub search_settings: SearchSettings,
+    pub search_results: SearchResults,
+    sfx_search_index: SearchIndex,
+    music_search_index: SearchIndex,
     pub music_filters: MusicFilters,
     pub audio_settings: AudioSettings,
 
@@ -48,14 +80,21 @@ pub struct AppState {
     pub unlisted_music: Vec<EntryId>,
 
     pub tool_progress: Arc<Mutex<Option<ToolProgress>>>,
+    /// Result of the last finished batch tool which isn't part of the download queue.
+    pub tool_report: Arc<Mutex<Option<ToolReport>>>,
+    pub download_queue: DownloadQueue,
+    /// Result of the last finished [`AppState::verify_files`].
+    pub verification_report: Arc<Mutex<Option<VerificationReport>>>,
 
     #[educe(Default = (0, 14500))]
     pub download_id_range_sfx: (EntryId, EntryId),
     #[educe(Default = (10000000, 10010000))]
     pub download_id_range_music: (EntryId, EntryId),
 
-    // TODO https://docs.rs/notify/6.1.1/notify/
-    // to keep track of externally added and removed SFX?
+    /// Keeps the downloaded and unlisted entries in sync with the GD folder, see [`AppState::sync_gd_folder`].
+    pub folder_watcher: FolderWatcher,
+    library_sfx: HashSet<EntryId>,
+    library_music: HashSet<EntryId>,
     downloaded_sfx: Arc<Mutex<HashSet<EntryId>>>,
     sfx_cache: Arc<Mutex<HashMap<EntryId, Vec<u8>>>>,
 
@@ -63,72 +102,63 @@ pub struct AppState {
     music_cache: Arc<Mutex<HashMap<EntryId, Vec<u8>>>>,
 
     pub konami: Konami,
+
+    /// Shown in the "What's new" window until it's closed.
+    pub library_updates: LibraryUpdates,
+    pub sfx_validation: ValidationReport,
 }
 
 impl AppState {
-    pub fn load(settings: PersistentSettings, sfx_library: &SfxLibrary, music_library: &MusicLibrary) -> Self {
-        let (downloaded_sfx, downloaded_music): (HashSet<EntryId>, HashSet<EntryId>) =
-            files::read_dir(&settings.gd_folder).into_iter().flatten()
-                .flat_map(|file| file.file_name().into_string())
-                .filter(|name| name.ends_with(".ogg"))
-                .filter_map(|name| {
-                    let is_sfx = name.starts_with('s');
-                    let id = name[is_sfx as usize..name.len() - 4].parse::<EntryId>().ok()?;
-                    Some((is_sfx, id))
-                })
-                .partition_map(|(is_sfx, id)| match is_sfx {
-                    true => Either::Left(id),
-                    false => Either::Right(id),
-                });
-
-        // TODO how do we want to update unlisted_sfx? a fn register_sfx(&mut self, id: EntryId, library?)
-        // and/or can unlisted_sfx be (partially) refactored into gdsfx-library?
-        // additional things to consider:
-        // - favorites tab
-        // - tools (un)registering sfx ids → thread safety
-        // - storing unlisted sfx? or only show downloaded ones
-        let library_sfx = sfx_library.sound_ids().iter().copied().collect();
-        let library_music = music_library.songs.keys().copied().collect();
-        let unlisted_sfx = downloaded_sfx.difference(&library_sfx).copied().collect();
-        let unlisted_music = downloaded_music.difference(&library_music).copied().collect();
-
+    pub fn load(
+        settings: PersistentSettings,
+        content_source: Arc<dyn ContentSource>,
+        sfx_library: &SfxLibrary,
+        music_library: &MusicLibrary,
+    ) -> Self {
         Self {
             settings,
-            favorites: Favorites::load(),
-            downloaded_sfx: Arc::new(Mutex::new(downloaded_sfx)),
-            downloaded_music: Arc::new(Mutex::new(downloaded_music)),
-            unlisted_sfx,
-            unlisted_music,
+            content_source,
+            favorites: Favorites::load(sfx_library, music_library),
+            smart_collections: SmartCollections::load(),
+            download_queue: DownloadQueue::load(),
+            library_sfx: sfx_library.sound_ids().iter().copied().collect(),
+            library_music: music_library.songs.keys().copied().collect(),
+            sfx_validation: sfx_library.validate(),
+            sfx_search_index: SearchIndex::for_sfx(sfx_library),
+            music_search_index: SearchIndex::for_music(music_library),
             ..Default::default()
         }
     }
 
+    /// Whether a sound, or any sound within a category, matches the search.
     pub fn is_matching_entry(&self, entry: &SfxLibraryEntry, sfx_library: &SfxLibrary) -> bool {
-        match &entry.kind {
-            EntryKind::Category => {
-                sfx_library
-                    .iter_children(entry)
-                    .any(|child| self.is_matching_entry(child, sfx_library))
-            }
-
-            EntryKind::Sound { .. } => {
-                if self.search_settings.show_downloaded && !self.is_sfx_downloaded(entry.id) {
-                    return false
-                }
+        sfx_library.iter_depth_first(entry)
+            .any(|entry| matches!(entry.kind, EntryKind::Sound { .. }) && self.is_matching_sound(entry, sfx_library))
+    }
 
-                let search = self.search_settings.search_query.to_lowercase();
-                entry.name.to_lowercase().contains(&search) || entry.id.to_string() == search
-            }
+    fn is_matching_sound(&self, entry: &SfxLibraryEntry, sfx_library: &SfxLibrary) -> bool {
+        let downloaded = self.is_sfx_downloaded(entry.id);
+        if self.search_settings.show_downloaded && !downloaded {
+            return false
         }
+
+        let state = EntryState { downloaded, favorite: self.favorites.has_favorite(FavoriteKey::sfx(entry.id)) };
+        self.search_results.is_matching_sfx(entry, sfx_library, state)
     }
 
-    pub fn is_matching_song(&self, song: &music::Song) -> bool {
-        if self.search_settings.show_downloaded && !self.is_music_downloaded(song.id) {
+    pub fn is_matching_song(&self, song: &music::Song, music_library: &MusicLibrary) -> bool {
+        let downloaded = self.is_music_downloaded(song.id);
+        if self.search_settings.show_downloaded && !downloaded {
             return false
         }
 
-        let search = self.search_settings.search_query.to_lowercase();
-        song.name.to_lowercase().contains(&search) || song.id.to_string() == search
+        let state = EntryState { downloaded, favorite: self.favorites.has_favorite(FavoriteKey::song(song.id)) };
+        self.search_results.is_matching_song(song, music_library, state)
+    }
+
+    /// Switches to the content source configured in the settings, e.g. after the mirror URL was changed.
+    pub fn update_content_source(&mut self) {
+        self.content_source = self.settings.create_content_source();
     }
 
     pub fn is_gd_folder_valid(&self) -> bool {
@@ -136,6 +166,10 @@ impl AppState {
         path.is_absolute() && path.is_dir()
     }
 
+    pub fn is_offline(&self) -> bool {
+        self.content_source.is_offline()
+    }
+
     pub fn is_tool_running(&self) -> bool {
         self.tool_progress.lock().is_some()
     }
@@ -148,6 +182,27 @@ impl AppState {
         self.downloaded_music.lock().contains(&id)
     }
 
+    /// Placeholders for the songs in [`Self::unlisted_music`], which are only known by their ID.
+    pub fn unlisted_songs(&self) -> impl Iterator<Item = music::Song> + '_ {
+        self.unlisted_music.iter().map(|&id| music::Song {
+            id,
+            name: id.to_string(),
+            credit_id: UNLISTED_ID,
+            ..Default::default()
+        })
+    }
+
+    /// IDs of the downloaded files of the current library page, sorted.
+    pub fn downloaded_ids(&self) -> Vec<EntryId> {
+        let downloaded = match self.library_page {
+            LibraryPage::Sfx => self.downloaded_sfx.lock(),
+            LibraryPage::Music => self.downloaded_music.lock(),
+        };
+        let mut ids = downloaded.iter().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+
     pub fn play_sound(&self, file_entry: impl FileEntry + 'static) {
         let cache = match file_entry.kind() {
             FileEntryKind::Sound => self.sfx_cache.clone(),
@@ -155,6 +210,7 @@ impl AppState {
         };
         
         let gd_folder = self.settings.gd_folder.clone();
+        let content_source = Arc::clone(&self.content_source);
         let audio_system = Arc::clone(&self.audio_system);
 
         thread::spawn(move || {
@@ -162,7 +218,7 @@ impl AppState {
                 let mut cache = cache.lock();
                 cache.get(&file_entry.id()).cloned().or_else(|| {
                     let bytes = file_entry.try_read_bytes(gd_folder)
-                        .or_else(|| file_entry.try_download_bytes());
+                        .or_else(|| file_entry.try_download_bytes(&*content_source).ok());
 
                     if let Some(bytes) = bytes.as_ref() {
                         cache.insert(file_entry.id(), bytes.clone());
@@ -178,34 +234,8 @@ impl AppState {
         });        
     }
 
-    pub fn download_sound(&self, file_entry: impl FileEntry + 'static) {
-        if !self.is_gd_folder_valid() { return }
-
-        let gd_folder = &self.settings.gd_folder;
-
-        if file_entry.file_exists(gd_folder) { return }
-
-        let cache = match file_entry.kind() {
-            FileEntryKind::Sound => self.sfx_cache.clone(),
-            FileEntryKind::Song => self.music_cache.clone(),
-        };
-        let downloaded = match file_entry.kind() {
-            FileEntryKind::Sound => self.downloaded_sfx.clone(),
-            FileEntryKind::Song => self.downloaded_music.clone(),
-        };
-
-        let gd_folder = gd_folder.clone();
-        let gd_folder = gd_folder.clone();
-
-        thread::spawn(move || {
-            let bytes = cache.lock().get(&file_entry.id()).cloned()
-                .or_else(|| file_entry.try_download_bytes());
-
-            let Some(bytes) = bytes else { return };
-            if file_entry.try_write_bytes(gd_folder, bytes).is_ok() {
-                downloaded.lock().insert(file_entry.id());
-            }
-        });
+    pub fn download_sound(&self, file_entry: impl FileEntry) {
+        self.queue_downloads([file_entry]);
     }
 
     pub fn delete_sound(&self, file_entry: impl FileEntry) {
@@ -227,7 +257,7 @@ impl AppState {
 }
 
 localized_enum! {
-    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter)]
+    #[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter)]
     pub enum LibraryPage = "library_page" {
         #[default]
         Sfx = "sfx",
@@ -238,6 +268,19 @@ localized_enum! {
 pub fn update(ctx: &egui::Context, app_state: &mut AppState) {
     app_state.konami.update(ctx);
 
+    app_state.sync_gd_folder(ctx);
+
+    if app_state.download_queue.is_stalled() {
+        app_state.start_downloads();
+    }
+    app_state.download_queue.try_save_if_changed();
+
+    app_state.search_results.update(
+        &app_state.search_settings.search_query,
+        &app_state.sfx_search_index,
+        &app_state.music_search_index,
+    );
+
     use crate::theme::*;
 
     ctx.set_visuals(match app_state.settings.theme {
@@ -252,7 +295,8 @@ pub fn update(ctx: &egui::Context, app_state: &mut AppState) {
 
 pub fn request_optional_repaint(ctx: &egui::Context, app_state: &mut AppState) {
     if 
-        app_state.tool_progress.lock().is_some()
+        app_state.is_tool_running()
+        || app_state.download_queue.is_active()
         || layout::debug_window::DEBUG_MODE.lock().is_some()
     {
         ctx.request_repaint();
@@ -1,54 +1,504 @@
-use std::{cmp::Ordering, time::Duration};
+use std::{cmp::Ordering, fmt::Display, str::FromStr, time::Duration};
 
-use ahash::HashSet;
+use ahash::{HashMap, HashSet};
+use serde::{Serialize, Deserialize};
 use strum::EnumIter;
 
 use library::music::{Song, TagId};
 use library::sfx::{EntryKind, SfxLibraryEntry};
-use library::{BytesSize, EntryId};
+use library::search::SearchIndex;
+use library::{BytesSize, EntryId, MusicLibrary, SfxLibrary};
 
 use crate::localized_enum;
 
-#[derive(Default, Debug)]
+use super::LibraryPage;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
 pub struct SearchSettings {
     pub search_query: String,
-    pub sorting_mode: SortingMode,
     pub show_downloaded: bool,
 }
 
+/// The parsed search query and relevance scores of the entries matching its text,
+/// only recomputed when the query changes.
+#[derive(Default, Debug)]
+pub struct SearchResults {
+    raw_query: String,
+    query: SearchQuery,
+    error: Option<QueryError>,
+    sfx: HashMap<EntryId, f32>,
+    music: HashMap<EntryId, f32>,
+}
+
+impl SearchResults {
+    pub fn update(&mut self, raw_query: &str, sfx_index: &SearchIndex, music_index: &SearchIndex) {
+        if self.raw_query == raw_query { return }
+
+        // invalid queries don't filter anything until they're fixed
+        (self.query, self.error) = match raw_query.parse() {
+            Ok(query) => (query, None),
+            Err(error) => (SearchQuery::default(), Some(error)),
+        };
+
+        let text = &self.query.text;
+        let scores = |index: &SearchIndex| index.search(text).into_iter()
+            .map(|result| (result.id, result.score))
+            .collect();
+
+        self.sfx = scores(sfx_index);
+        self.music = scores(music_index);
+        self.raw_query = raw_query.to_string();
+    }
+
+    /// Why the query is invalid, including filters which don't apply to `page`.
+    pub fn error(&self, page: LibraryPage) -> Option<QueryError> {
+        self.error.clone().or_else(|| self.query.check_page(page).err())
+    }
+
+    /// Whether the results are ranked by relevance.
+    pub fn is_active(&self) -> bool {
+        !self.query.text.is_empty()
+    }
+
+    pub fn is_matching_sfx(&self, entry: &SfxLibraryEntry, library: &SfxLibrary, state: EntryState) -> bool {
+        // like invalid queries, filters which don't apply to the page don't filter anything
+        if self.query.check_page(LibraryPage::Sfx).is_err() { return true }
+
+        self.is_matching_text(&self.sfx, entry.id)
+            && self.query.filters.iter().all(|filter| filter.matches_sfx(entry, library, state))
+    }
+
+    pub fn is_matching_song(&self, song: &Song, library: &MusicLibrary, state: EntryState) -> bool {
+        if self.query.check_page(LibraryPage::Music).is_err() { return true }
+
+        self.is_matching_text(&self.music, song.id)
+            && self.query.filters.iter().all(|filter| filter.matches_song(song, library, state))
+    }
+
+    fn is_matching_text(&self, scores: &HashMap<EntryId, f32>, id: EntryId) -> bool {
+        // unlisted entries aren't indexed, but can still be found by their ID
+        !self.is_active() || scores.contains_key(&id) || id.to_string() == self.query.text
+    }
+
+    pub fn music_score(&self, id: EntryId) -> f32 {
+        self.music.get(&id).copied().unwrap_or_default()
+    }
+}
+
+/// A parsed search query, e.g. `explosion duration:<2s downloaded:yes`.
+/// Words without a known filter prefix are looked up in the search index, every filter has to match.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SearchQuery {
+    pub text: String,
+    pub filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// Lowercase part of the name of one of the artists of a song.
+    Artist(String),
+    /// Lowercase part of the name of one of the tags of a song.
+    Tag(String),
+    /// Lowercase part of the name of one of the categories containing a sound.
+    Category(String),
+    Duration(Comparison<Duration>),
+    Size(Comparison<BytesSize>),
+    Id(Comparison<EntryId>),
+    Downloaded(bool),
+    Favorite(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison<T> {
+    Equal(T),
+    Less(T),
+    LessOrEqual(T),
+    Greater(T),
+    GreaterOrEqual(T),
+    /// Inclusive on both ends.
+    Range(T, T),
+}
+
+impl<T: PartialOrd> Comparison<T> {
+    pub fn matches(&self, value: T) -> bool {
+        match self {
+            Self::Equal(other) => &value == other,
+            Self::Less(other) => &value < other,
+            Self::LessOrEqual(other) => &value <= other,
+            Self::Greater(other) => &value > other,
+            Self::GreaterOrEqual(other) => &value >= other,
+            Self::Range(start, end) => start <= &value && &value <= end,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    UnterminatedQuote,
+    /// A filter which only applies to the other library page.
+    InapplicableFilter(String),
+    MissingValue(String),
+    InvalidValue { filter: String, value: String },
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            Self::UnterminatedQuote => t!("search.error.unterminated_quote"),
+            Self::InapplicableFilter(filter) => t!("search.error.inapplicable_filter", filter = filter),
+            Self::MissingValue(filter) => t!("search.error.missing_value", filter = filter),
+            Self::InvalidValue { filter, value } => t!("search.error.invalid_value", filter = filter, value = value),
+        };
+        f.write_str(&message)
+    }
+}
+
+/// Download and favorite state of an entry, which filters can't look up in the libraries.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryState {
+    pub downloaded: bool,
+    pub favorite: bool,
+}
+
+impl SearchQuery {
+    /// Fails with the first filter which doesn't apply to `page`.
+    pub fn check_page(&self, page: LibraryPage) -> Result<(), QueryError> {
+        match self.filters.iter().find(|filter| !filter.applies_to(page)) {
+            Some(filter) => Err(QueryError::InapplicableFilter(filter.key().to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Filter {
+    /// The key of the filter in queries.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Artist(_) => "artist",
+            Self::Tag(_) => "tag",
+            Self::Category(_) => "category",
+            Self::Duration(_) => "duration",
+            Self::Size(_) => "size",
+            Self::Id(_) => "id",
+            Self::Downloaded(_) => "downloaded",
+            Self::Favorite(_) => "favorite",
+        }
+    }
+
+    pub fn applies_to(&self, page: LibraryPage) -> bool {
+        match self {
+            Self::Artist(_) | Self::Tag(_) => page == LibraryPage::Music,
+            Self::Category(_) => page == LibraryPage::Sfx,
+            _ => true,
+        }
+    }
+
+    pub fn matches_sfx(&self, entry: &SfxLibraryEntry, library: &SfxLibrary, state: EntryState) -> bool {
+        match self {
+            Self::Artist(_) | Self::Tag(_) => false,
+            Self::Category(name) => library.ancestors(entry.id)
+                .filter(|category| category.id != library.root_id)
+                .any(|category| category.name.to_lowercase().contains(name)),
+            Self::Duration(comparison) => comparison.matches(entry.get_duration()),
+            Self::Size(comparison) => comparison.matches(entry.get_bytes()),
+            Self::Id(comparison) => comparison.matches(entry.id),
+            Self::Downloaded(downloaded) => state.downloaded == *downloaded,
+            Self::Favorite(favorite) => state.favorite == *favorite,
+        }
+    }
+
+    pub fn matches_song(&self, song: &Song, library: &MusicLibrary, state: EntryState) -> bool {
+        match self {
+            Self::Artist(name) => song.artist_ids()
+                .flat_map(|id| library.credits.get(&id))
+                .any(|credit| credit.name.to_lowercase().contains(name)),
+            Self::Tag(name) => song.tags.iter()
+                .flat_map(|id| library.tags.get(id))
+                .any(|tag| tag.name.to_lowercase().contains(name)),
+            Self::Category(_) => false,
+            Self::Duration(comparison) => comparison.matches(song.duration),
+            Self::Size(comparison) => comparison.matches(song.bytes),
+            Self::Id(comparison) => comparison.matches(song.id),
+            Self::Downloaded(downloaded) => state.downloaded == *downloaded,
+            Self::Favorite(favorite) => state.favorite == *favorite,
+        }
+    }
+}
+
+impl FromStr for SearchQuery {
+    type Err = QueryError;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let mut words = Vec::new();
+        let mut filters = Vec::new();
+
+        for term in split_terms(query)? {
+            let Some(key) = term.key else {
+                if !term.value.is_empty() {
+                    words.push(term.value);
+                }
+                continue
+            };
+
+            match parse_filter(&key, term.value.trim()) {
+                Some(filter) => filters.push(filter?),
+                // e.g. `Song: Intro`, which is searched for like any other words
+                None => words.push(format!("{key}:{}", term.value)),
+            }
+        }
+
+        Ok(Self { text: words.join(" "), filters })
+    }
+}
+
+/// Parses the filter `key:value`, or returns `None` if there's no filter called `key`.
+fn parse_filter(key: &str, value: &str) -> Option<Result<Filter, QueryError>> {
+    let invalid = || QueryError::InvalidValue { filter: key.to_string(), value: value.to_string() };
+
+    let filter = match key.to_lowercase().as_str() {
+        "artist" => Ok(Filter::Artist(value.to_lowercase())),
+        "tag" => Ok(Filter::Tag(value.to_lowercase())),
+        "category" => Ok(Filter::Category(value.to_lowercase())),
+        "duration" | "length" => parse_comparison(value, parse_duration).map(Filter::Duration).ok_or_else(invalid),
+        "size" => parse_comparison(value, parse_size).map(Filter::Size).ok_or_else(invalid),
+        "id" => parse_comparison(value, |id| id.parse().ok()).map(Filter::Id).ok_or_else(invalid),
+        "downloaded" => parse_bool(value).map(Filter::Downloaded).ok_or_else(invalid),
+        "fav" | "favorite" => parse_bool(value).map(Filter::Favorite).ok_or_else(invalid),
+        _ => return None,
+    };
+
+    Some(if value.is_empty() { Err(QueryError::MissingValue(key.to_string())) } else { filter })
+}
+
+#[derive(Debug, Default)]
+struct Term {
+    key: Option<String>,
+    value: String,
+    quoted: bool,
+}
+
+/// Splits a query at whitespace outside of double quotes, e.g. `artist:"Dex Arson" boom`.
+/// A term is a filter if it starts with letters followed by a colon.
+fn split_terms(query: &str) -> Result<Vec<Term>, QueryError> {
+    let mut terms = Vec::new();
+    let mut term: Option<Term> = None;
+    let mut quoted = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                term.get_or_insert_with(Term::default).quoted = true;
+            }
+            c if c.is_whitespace() && !quoted => terms.extend(term.take()),
+            ':' if !quoted => {
+                let term = term.get_or_insert_with(Term::default);
+                let is_key = term.key.is_none()
+                    && !term.quoted
+                    && !term.value.is_empty()
+                    && term.value.chars().all(|c| c.is_ascii_alphabetic());
+
+                if is_key {
+                    term.key = Some(std::mem::take(&mut term.value));
+                } else {
+                    term.value.push(c);
+                }
+            }
+            c => term.get_or_insert_with(Term::default).value.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(QueryError::UnterminatedQuote)
+    }
+    terms.extend(term);
+
+    Ok(terms)
+}
+
+/// `a..b`, `a..`, `..b`, `<a`, `<=a`, `>a`, `>=a`, `=a` or just `a`.
+fn parse_comparison<T>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Option<Comparison<T>> {
+    if let Some((start, end)) = value.split_once("..") {
+        return match (start.is_empty(), end.is_empty()) {
+            (true, true) => None,
+            (true, false) => parse(end).map(Comparison::LessOrEqual),
+            (false, true) => parse(start).map(Comparison::GreaterOrEqual),
+            (false, false) => Some(Comparison::Range(parse(start)?, parse(end)?)),
+        }
+    }
+
+    let (comparison, operand): (fn(T) -> Comparison<T>, &str) =
+        if let Some(operand) = value.strip_prefix("<=") { (Comparison::LessOrEqual, operand) }
+        else if let Some(operand) = value.strip_prefix(">=") { (Comparison::GreaterOrEqual, operand) }
+        else if let Some(operand) = value.strip_prefix('<') { (Comparison::Less, operand) }
+        else if let Some(operand) = value.strip_prefix('>') { (Comparison::Greater, operand) }
+        else { (Comparison::Equal, value.strip_prefix('=').unwrap_or(value)) };
+
+    parse(operand).map(comparison)
+}
+
+/// Splits e.g. `1.5MB` into `1.5` and `mb`.
+fn split_unit(value: &str) -> Option<(f64, String)> {
+    let index = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let number = value[..index].parse().ok()?;
+    Some((number, value[index..].to_lowercase()))
+}
+
+/// Seconds by default, e.g. `30`, `500ms`, `2.5s` or `1m`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit) = split_unit(value)?;
+    let seconds = match unit.as_str() {
+        "ms" => 0.001,
+        "" | "s" | "sec" => 1.0,
+        "m" | "min" => 60.0,
+        "h" => 3600.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(number * seconds).ok()
+}
+
+/// Bytes by default, e.g. `500`, `20kb` or `1.5MB`.
+fn parse_size(value: &str) -> Option<BytesSize> {
+    let (number, unit) = split_unit(value)?;
+    let bytes = match unit.as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" => 1e3,
+        "m" | "mb" => 1e6,
+        "g" | "gb" => 1e9,
+        _ => return None,
+    };
+    Some((number * bytes).round() as BytesSize)
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "yes" | "y" | "true" => Some(true),
+        "no" | "n" | "false" => Some(false),
+        _ => None,
+    }
+}
+
 localized_enum! {
-    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter)]
-    pub enum SortingMode = "search.sort" {
+    #[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, EnumIter)]
+    pub enum SortKey = "search.sort" {
         #[default]
-        NameInc = "name.ascending",      // a - z
-        NameDec = "name.descending",     // z - a
-        LengthInc = "length.ascending",  // 0.00 - 1.00
-        LengthDec = "length.descending", // 1.00 - 0.00
-        IdInc = "id.ascending",          // 0 - 9
-        IdDec = "id.descending",         // 9 - 0
-        SizeInc = "size.ascending",      // 0kb - 9kb
-        SizeDec = "size.descending",     // 9kb - 0kb
+        Name = "name",
+        Length = "length",
+        Id = "id",
+        Size = "size",
+        Artist = "artist",
+        Tag = "tag",
+        New = "new",
+        Ncs = "ncs",
     }
 }
 
-impl SortingMode {
+impl SortKey {
+    /// Keys which only apply to songs.
+    pub fn is_music_only(&self) -> bool {
+        matches!(self, Self::Artist | Self::Tag | Self::New | Self::Ncs)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct SortCriterion {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+/// Sort keys in order of priority; later keys only decide between entries which are equal by all earlier keys.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SortOrder(pub Vec<SortCriterion>);
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        Self(vec![SortCriterion::default()])
+    }
+}
+
+impl SortOrder {
+    /// Sorts by the keys which don't need the music library. Categories are always on top.
     pub fn compare_entries(&self, a: &impl EntrySorting, b: &impl EntrySorting) -> Ordering {
-        b.is_category().cmp(&a.is_category()) // categories on top
-            .then(match self {
-                Self::NameInc => a.get_name().cmp(b.get_name()),
-                Self::NameDec => b.get_name().cmp(a.get_name()),
-                Self::LengthInc => a.get_duration().cmp(&b.get_duration()),
-                Self::LengthDec => b.get_duration().cmp(&a.get_duration()),
-                Self::IdInc => a.get_id().cmp(&b.get_id()),
-                Self::IdDec => b.get_id().cmp(&a.get_id()),
-                Self::SizeInc => a.get_bytes().cmp(&b.get_bytes()),
-                Self::SizeDec => b.get_bytes().cmp(&a.get_bytes()),
+        self.compare(a, b, None)
+    }
+
+    pub fn compare_songs(&self, a: &Song, b: &Song, library: &MusicLibrary) -> Ordering {
+        self.compare(a, b, Some(library))
+    }
+
+    fn compare(&self, a: &impl EntrySorting, b: &impl EntrySorting, library: Option<&MusicLibrary>) -> Ordering {
+        let ordering = self.0.iter()
+            .map(|criterion| {
+                let ordering = match criterion.key {
+                    SortKey::Name => natural_cmp(a.get_name(), b.get_name()),
+                    SortKey::Length => a.get_duration().cmp(&b.get_duration()),
+                    SortKey::Id => a.get_id().cmp(&b.get_id()),
+                    SortKey::Size => a.get_bytes().cmp(&b.get_bytes()),
+                    SortKey::Artist => compare_names(a.get_artist_name(library), b.get_artist_name(library)),
+                    SortKey::Tag => compare_names(a.get_tag_name(library), b.get_tag_name(library)),
+                    SortKey::New => a.is_new().cmp(&b.is_new()),
+                    SortKey::Ncs => a.is_ncs().cmp(&b.is_ncs()),
+                };
+                if criterion.descending { ordering.reverse() } else { ordering }
             })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal);
+
+        b.is_category().cmp(&a.is_category()) // categories on top
+            .then(ordering)
+    }
+}
+
+/// Entries without a name go last.
+fn compare_names(a: Option<&str>, b: Option<&str>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => natural_cmp(a, b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    }
+}
+
+/// Compares numbers within names by their value, so `Hit 2` comes before `Hit 10`.
+/// Letters are compared case-insensitively first.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b), // e.g. "hit" and "Hit"
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_number = take_number(&mut a_chars);
+                let y_number = take_number(&mut b_chars);
+
+                // longer numbers without leading zeros are larger
+                let ordering = x_number.trim_start_matches('0').len().cmp(&y_number.trim_start_matches('0').len())
+                    .then_with(|| x_number.trim_start_matches('0').cmp(y_number.trim_start_matches('0')));
+                if ordering.is_ne() { return ordering }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering.is_ne() { return ordering }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        number.push(c);
     }
+    number
 }
 
 localized_enum! {
-    #[derive(EnumIter, Default, Debug, PartialEq, Eq, Clone, Copy)]
+    #[derive(Serialize, Deserialize, EnumIter, Default, Debug, PartialEq, Eq, Clone, Copy)]
     pub enum ListedMode = "listed_mode" {
         #[default]
         Listed = "listed",
@@ -56,18 +506,34 @@ localized_enum! {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
 pub struct MusicFilters {
     pub artists: HashSet<EntryId>,
     pub tags: HashSet<TagId>,
+    pub only_new: bool,
     pub listed_mode: ListedMode,
 }
 
+impl MusicFilters {
+    /// Whether a listed song has all selected tags, one of the selected artists and is new if required.
+    pub fn matches(&self, song: &Song) -> bool {
+        self.tags.iter().all(|tag| song.tags.contains(tag))
+            && (self.artists.is_empty() || song.artist_ids().any(|id| self.artists.contains(&id)))
+            && (!self.only_new || song.new)
+    }
+}
+
 pub trait EntrySorting {
     fn get_name(&self) -> &str;
     fn get_id(&self) -> EntryId;
     fn get_duration(&self) -> Duration;
     fn get_bytes(&self) -> BytesSize;
+    fn get_artist_name<'a>(&self, _library: Option<&'a MusicLibrary>) -> Option<&'a str> { None }
+    /// The alphabetically first tag name.
+    fn get_tag_name<'a>(&self, _library: Option<&'a MusicLibrary>) -> Option<&'a str> { None }
+    fn is_new(&self) -> bool { false }
+    fn is_ncs(&self) -> bool { false }
     fn is_category(&self) -> bool { false }
 }
 
@@ -76,6 +542,18 @@ impl EntrySorting for Song {
     fn get_id(&self) -> EntryId { self.id }
     fn get_duration(&self) -> Duration { self.duration }
     fn get_bytes(&self) -> BytesSize { self.bytes }
+    fn get_artist_name<'a>(&self, library: Option<&'a MusicLibrary>) -> Option<&'a str> {
+        library?.credits.get(&self.credit_id).map(|credit| credit.name.as_str())
+    }
+    fn get_tag_name<'a>(&self, library: Option<&'a MusicLibrary>) -> Option<&'a str> {
+        let library = library?;
+        self.tags.iter()
+            .flat_map(|id| library.tags.get(id))
+            .map(|tag| tag.name.as_str())
+            .min_by(|a, b| natural_cmp(a, b))
+    }
+    fn is_new(&self) -> bool { self.new }
+    fn is_ncs(&self) -> bool { self.ncs }
 }
 
 impl EntrySorting for SfxLibraryEntry {
@@ -85,3 +563,86 @@ impl EntrySorting for SfxLibraryEntry {
     fn get_bytes(&self) -> BytesSize { self.bytes().unwrap_or_default() }
     fn is_category(&self) -> bool { matches!(self.kind, EntryKind::Category) }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(query: &str) -> Result<SearchQuery, QueryError> {
+        query.parse()
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse(r#"fire artist:"Dex Arson" tag:action duration:<30s size:>1MB id:4000..5000 downloaded:yes category:explosions fav:no hole"#).unwrap();
+
+        assert_eq!(query.text, "fire hole");
+        assert_eq!(query.filters, vec![
+            Filter::Artist("dex arson".to_string()),
+            Filter::Tag("action".to_string()),
+            Filter::Duration(Comparison::Less(Duration::from_secs(30))),
+            Filter::Size(Comparison::Greater(1_000_000)),
+            Filter::Id(Comparison::Range(4000, 5000)),
+            Filter::Downloaded(true),
+            Filter::Category("explosions".to_string()),
+            Filter::Favorite(false),
+        ]);
+
+        assert_eq!(parse(r#""fire in: the hole" 10:30"#).unwrap(), SearchQuery {
+            text: "fire in: the hole 10:30".to_string(),
+            filters: vec![],
+        });
+        assert_eq!(parse("id:..100 length:1.5m").unwrap().filters, vec![
+            Filter::Id(Comparison::LessOrEqual(100)),
+            Filter::Duration(Comparison::Equal(Duration::from_secs(90))),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_query_errors() {
+        assert_eq!(parse(r#"artist:"Dex"#), Err(QueryError::UnterminatedQuote));
+        assert_eq!(parse("tag: action"), Err(QueryError::MissingValue("tag".to_string())));
+        assert_eq!(parse("size:>1XB"), Err(QueryError::InvalidValue { filter: "size".to_string(), value: ">1XB".to_string() }));
+        assert_eq!(parse("downloaded:maybe"), Err(QueryError::InvalidValue { filter: "downloaded".to_string(), value: "maybe".to_string() }));
+    }
+
+    #[test]
+    fn test_parse_query_unknown_filters() {
+        assert_eq!(parse("Song: Intro").unwrap(), SearchQuery {
+            text: "Song: Intro".to_string(),
+            filters: vec![],
+        });
+        assert_eq!(parse("foo:bar size:>1MB").unwrap(), SearchQuery {
+            text: "foo:bar".to_string(),
+            filters: vec![Filter::Size(Comparison::Greater(1_000_000))],
+        });
+    }
+
+    #[test]
+    fn test_query_page() {
+        let query = parse("artist:waterflame").unwrap();
+        assert_eq!(query.check_page(LibraryPage::Music), Ok(()));
+        assert_eq!(query.check_page(LibraryPage::Sfx), Err(QueryError::InapplicableFilter("artist".to_string())));
+
+        let query = parse("Category:explosions size:>1MB").unwrap();
+        assert_eq!(query.check_page(LibraryPage::Sfx), Ok(()));
+        assert_eq!(query.check_page(LibraryPage::Music), Err(QueryError::InapplicableFilter("category".to_string())));
+    }
+
+    #[test]
+    fn test_comparison() {
+        assert!(Comparison::Range(1, 3).matches(3));
+        assert!(!Comparison::Range(1, 3).matches(4));
+        assert!(Comparison::GreaterOrEqual(2).matches(2));
+        assert!(!Comparison::Less(2).matches(2));
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("Hit 2", "Hit 10"), Ordering::Less);
+        assert_eq!(natural_cmp("Hit 10", "Hit 02"), Ordering::Greater);
+        assert_eq!(natural_cmp("hit", "Hit 1"), Ordering::Less);
+        assert_eq!(natural_cmp("boom", "Bang"), Ordering::Greater);
+        assert_eq!(natural_cmp("Hit", "hit"), Ordering::Less);
+    }
+}
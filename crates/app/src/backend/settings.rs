@@ -1,22 +1,34 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use educe::Educe;
 use once_cell::sync::Lazy;
 use serde::{Serialize, Deserialize};
 use strum::EnumIter;
 
+use library::{ContentSource, HttpSource, OfflineSource};
+
 use crate::localized_enum;
 
+use super::LibraryPage;
+use super::search::SortOrder;
+
 static SETTINGS_FILE: Lazy<PathBuf> = Lazy::new(|| {
     files::paths::PROJECT_DIR.config_local_dir().join("settings.json")
 });
 
 #[derive(Educe, Serialize, Deserialize, Debug)]
 #[educe(Default, Clone, PartialEq)]
+#[serde(default)] // keep existing settings when new ones are added
 pub struct PersistentSettings {
     #[educe(Default = get_gd_folder())]
     pub gd_folder: String,
 
+    /// Base URL of a mirror to download files from, or empty for the official servers.
+    pub content_url: String,
+
+    /// Never access the network; only use local library files and downloaded sounds.
+    pub offline_mode: bool,
+
     pub search_filter_mode: SearchFilterMode,
 
     pub sfx_select_mode: SelectMode,
@@ -30,6 +42,17 @@ pub struct PersistentSettings {
     #[educe(Default = ColorTheme::Dark)]
     pub theme: ColorTheme,
 
+    pub sfx_sort_order: SortOrder,
+    pub music_sort_order: SortOrder,
+
+    /// Number of files downloaded at the same time.
+    #[educe(Default = 4)]
+    pub download_concurrency: usize,
+
+    /// How often a download is retried after a network or server error.
+    #[educe(Default = 3)]
+    pub download_retries: u32,
+
     #[serde(skip)]
     #[educe(Clone(method(ignore_option)), PartialEq(ignore))]
     last_state: Option<Box<PersistentSettings>>,
@@ -93,6 +116,32 @@ impl PersistentSettings {
         }
     }
 
+    pub fn sort_order(&self, page: LibraryPage) -> &SortOrder {
+        match page {
+            LibraryPage::Sfx => &self.sfx_sort_order,
+            LibraryPage::Music => &self.music_sort_order,
+        }
+    }
+
+    pub fn sort_order_mut(&mut self, page: LibraryPage) -> &mut SortOrder {
+        match page {
+            LibraryPage::Sfx => &mut self.sfx_sort_order,
+            LibraryPage::Music => &mut self.music_sort_order,
+        }
+    }
+
+    pub fn create_content_source(&self) -> Arc<dyn ContentSource> {
+        if self.offline_mode {
+            return Arc::new(OfflineSource)
+        }
+        if !self.content_url.trim().is_empty() {
+            if let Ok(source) = HttpSource::with_base_url(&self.content_url) {
+                return Arc::new(source)
+            }
+        }
+        Arc::new(HttpSource::official())
+    }
+
     fn has_changed(&self) -> bool {
         self.last_state.as_ref()
             .map(|last| self.ne(last))
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serde::{Serialize, Deserialize};
+
+use super::{AppState, LibraryPage};
+use super::search::{MusicFilters, SearchSettings, SortOrder};
+
+static SMART_COLLECTIONS_FILE: Lazy<PathBuf> = Lazy::new(|| {
+    files::paths::PROJECT_DIR.config_local_dir().join("smart_collections.json")
+});
+
+/// A named search which can be applied again later.
+/// Only the search is stored, not its results, so it always shows the current library content.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct SmartCollection {
+    pub name: String,
+    pub library_page: LibraryPage,
+    pub search_settings: SearchSettings,
+    pub music_filters: MusicFilters,
+    /// Sort order of the library page.
+    pub sort_order: SortOrder,
+}
+
+impl SmartCollection {
+    pub fn from_app_state(name: String, app_state: &AppState) -> Self {
+        Self {
+            name,
+            library_page: app_state.library_page,
+            search_settings: app_state.search_settings.clone(),
+            music_filters: app_state.music_filters.clone(),
+            sort_order: app_state.settings.sort_order(app_state.library_page).clone(),
+        }
+    }
+
+    pub fn apply(&self, app_state: &mut AppState) {
+        app_state.library_page = self.library_page;
+        app_state.search_settings = self.search_settings.clone();
+        app_state.music_filters = self.music_filters.clone();
+        *app_state.settings.sort_order_mut(self.library_page) = self.sort_order.clone();
+    }
+
+    /// Whether this collection is what's currently shown.
+    pub fn is_applied(&self, app_state: &AppState) -> bool {
+        self.library_page == app_state.library_page
+            && self.search_settings == app_state.search_settings
+            && &self.sort_order == app_state.settings.sort_order(self.library_page)
+            && (self.library_page == LibraryPage::Sfx || self.music_filters == app_state.music_filters)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SmartCollections(Vec<SmartCollection>);
+
+impl SmartCollections {
+    pub fn load() -> Self {
+        files::read_json(&*SMART_COLLECTIONS_FILE).unwrap_or_default()
+    }
+
+    fn try_save(&self) -> Result<()> {
+        let json_data = serde_json::to_string(self).expect("derived serialization shouldn't fail");
+
+        let _ = files::create_parent_dirs(&*SMART_COLLECTIONS_FILE);
+        files::write_file(&*SMART_COLLECTIONS_FILE, json_data)
+    }
+
+    /// Applies a change and saves it, undoing the change if saving fails.
+    fn update(&mut self, change: impl FnOnce(&mut Vec<SmartCollection>)) -> Result<()> {
+        let previous = self.0.clone();
+        change(&mut self.0);
+
+        self.try_save().inspect_err(|_| self.0 = previous)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SmartCollection> {
+        self.0.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SmartCollection> {
+        self.0.iter().find(|collection| collection.name == name)
+    }
+
+    /// Adds a collection, replacing the one with the same name if there is one.
+    pub fn save_collection(&mut self, collection: SmartCollection) -> Result<()> {
+        self.update(|collections| {
+            match collections.iter_mut().find(|existing| existing.name == collection.name) {
+                Some(existing) => *existing = collection,
+                None => collections.push(collection),
+            }
+        })
+    }
+
+    pub fn remove_collection(&mut self, name: &str) -> Result<()> {
+        self.update(|collections| collections.retain(|collection| collection.name != name))
+    }
+}
@@ -1,9 +1,14 @@
-use std::{thread, fs, time::Instant, sync::Arc};
+use std::{io, path::Path, thread, time::Instant, sync::Arc};
 
+use anyhow::Result;
 use eframe::egui::{Ui, ProgressBar};
+use parking_lot::Mutex;
 use rayon::prelude::*;
+use strum::EnumIter;
 
-use library::{FileEntry, FileEntryKind, MusicFileEntry, SfxFileEntry};
+use library::{EntryId, FileEntry, FileEntryKind, IntegrityError, LibraryError, MusicFileEntry, MusicLibrary, SfxFileEntry, SfxLibrary};
+
+use crate::{i18n::LocalizedEnum, localized_enum};
 
 use super::{AppState, LibraryPage};
 
@@ -47,71 +52,208 @@ impl ToolProgress {
     }
 }
 
-impl AppState {
-    pub fn download_multiple_sfx(&self, translation_key: String, files: Vec<impl FileEntry + 'static>) {
-        if !self.is_gd_folder_valid() { return }
+localized_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+    pub enum ItemResult = "tools.report" {
+        Succeeded = "succeeded",
+        /// E.g. the file was already downloaded, or already deleted.
+        Skipped = "skipped",
+        Failed = "failed",
+    }
+}
 
-        let progress = self.tool_progress.clone();
-        *progress.lock() = Some(ToolProgress::new(translation_key, files.len()));
+#[derive(Debug, Clone)]
+pub struct ReportItem {
+    pub kind: FileEntryKind,
+    pub id: EntryId,
+    pub result: ItemResult,
+    pub error: Option<String>,
+}
+
+impl ReportItem {
+    pub fn new(kind: FileEntryKind, id: EntryId, result: ItemResult, error: Option<LibraryError>) -> Self {
+        let error = error.map(|error| format!("{:#}", anyhow::Error::new(error)));
+        Self { kind, id, result, error }
+    }
+}
+
+/// What a batch tool did with every file, shown after it finished.
+#[derive(Debug, Clone)]
+pub struct ToolReport {
+    pub translation_key: String,
+    pub items: Vec<ReportItem>,
+}
+
+impl ToolReport {
+    pub fn count(&self, result: ItemResult) -> usize {
+        self.items.iter().filter(|item| item.result == result).count()
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &ReportItem> {
+        self.items.iter().filter(|item| item.result == ItemResult::Failed)
+    }
+
+    /// Writes every item as a CSV row.
+    pub fn export(&self, path: impl AsRef<Path>, sfx_library: &SfxLibrary, music_library: &MusicLibrary) -> Result<()> {
+        let mut csv = csv::Writer::from_path(path)?;
+        csv.write_record(["kind", "id", "name", "result", "error"])?;
 
-        let (download_cache, download_list) = match files[0].kind() {
-            FileEntryKind::Sound => (self.sfx_cache.clone(), self.downloaded_sfx.clone()),
-            FileEntryKind::Song => (self.music_cache.clone(), self.downloaded_music.clone()),
+        for item in &self.items {
+            let kind = match item.kind {
+                FileEntryKind::Sound => "sound",
+                FileEntryKind::Song => "song",
+            };
+            csv.write_record([
+                kind,
+                &item.id.to_string(),
+                entry_name(item.kind, item.id, sfx_library, music_library).unwrap_or_default(),
+                item.result.localization_key(),
+                item.error.as_deref().unwrap_or_default(),
+            ])?;
+        }
+
+        csv.flush()?;
+        Ok(())
+    }
+}
+
+/// Name of a sound or song, if it's in the library.
+pub fn entry_name<'a>(kind: FileEntryKind, id: EntryId, sfx_library: &'a SfxLibrary, music_library: &'a MusicLibrary) -> Option<&'a str> {
+    match kind {
+        FileEntryKind::Sound => sfx_library.entries().get(&id).map(|entry| entry.name.as_str()),
+        FileEntryKind::Song => music_library.songs.get(&id).map(|song| song.name.as_str()),
+    }
+}
+
+/// Files found by [`AppState::verify_files`] which don't match the library.
+pub struct VerificationReport {
+    pub kind: FileEntryKind,
+    pub checked: usize,
+    pub corrupt: Vec<(EntryId, IntegrityError)>,
+}
+
+impl AppState {
+    /// Deletes the downloaded files of the current library page.
+    pub fn delete_all_sfx(&self, translation_key: String) {
+        let kind = match self.library_page {
+            LibraryPage::Sfx => FileEntryKind::Sound,
+            LibraryPage::Music => FileEntryKind::Song,
         };
+        self.delete_files(translation_key, kind, self.downloaded_ids());
+    }
+
+    /// Tries the failed deletions of the last [`ToolReport`] again.
+    pub fn retry_failed_deletions(&self) {
+        let Some(report) = self.tool_report.lock().take() else { return };
+        let failed = report.failed().map(|item| (item.kind, item.id)).collect::<Vec<_>>();
+
+        let Some(&(kind, _)) = failed.first() else { return };
+        self.delete_files(report.translation_key, kind, failed.into_iter().map(|(_, id)| id).collect());
+    }
+
+    fn delete_files(&self, translation_key: String, kind: FileEntryKind, ids: Vec<EntryId>) {
+        let progress = Arc::clone(&self.tool_progress);
+        *progress.lock() = Some(ToolProgress::new(translation_key.clone(), ids.len()));
+
+        let report = Arc::clone(&self.tool_report);
+        *report.lock() = None;
+
         let gd_folder = self.settings.gd_folder.clone();
-    
+        let downloaded = match kind {
+            FileEntryKind::Sound => Arc::clone(&self.downloaded_sfx),
+            FileEntryKind::Song => Arc::clone(&self.downloaded_music),
+        };
+
         thread::spawn(move || {
-            files.into_par_iter().try_for_each(|file_entry| {
-                if !file_entry.file_exists(&gd_folder) {
-                    let bytes = download_cache.lock().get(&file_entry.id()).cloned()
-                        .or_else(|| file_entry.try_download_bytes());
-
-                    if let Some(bytes) = bytes {
-                        if file_entry.try_write_bytes(&gd_folder, bytes).is_ok() {
-                            download_list.lock().insert(file_entry.id());
-                        }
-                    }
+            let mut items = Vec::new();
+
+            ids.into_iter().try_for_each(|id| {
+                let result = match kind {
+                    FileEntryKind::Sound => SfxFileEntry::new(id).try_delete_file(&gd_folder),
+                    FileEntryKind::Song => MusicFileEntry::new(id).try_delete_file(&gd_folder),
+                };
+                let item = match result {
+                    Ok(()) => ReportItem::new(kind, id, ItemResult::Succeeded, None),
+                    Err(LibraryError::Io { source, .. }) if source.kind() == io::ErrorKind::NotFound =>
+                        ReportItem::new(kind, id, ItemResult::Skipped, None),
+                    Err(error) => ReportItem::new(kind, id, ItemResult::Failed, Some(error)),
+                };
+                if item.result != ItemResult::Failed {
+                    downloaded.lock().remove(&id);
                 }
+                items.push(item);
+
                 progress.lock().as_mut().map(|progress| progress.finished += 1)
             });
-    
+
+            // also report what was done before the tool was stopped
+            *report.lock() = Some(ToolReport { translation_key, items });
             *progress.lock() = None;
         });
     }
-    
-    pub fn delete_all_sfx(&self, translation_key: String) {
-        let Ok(read_dir) = fs::read_dir(&self.settings.gd_folder) else { return };
-        let read_dir = read_dir.flatten().collect::<Vec<_>>();
-        
+
+    /// Checks every file in `files` which exists in the GD folder, see [`FileEntry::verify_file`].
+    pub fn verify_files(&self, translation_key: String, files: Vec<impl FileEntry + 'static>) {
+        let Some(kind) = files.first().map(FileEntry::kind) else { return };
+
         let progress = Arc::clone(&self.tool_progress);
-        *progress.lock() = Some(ToolProgress::new(translation_key, read_dir.len()));
+        *progress.lock() = Some(ToolProgress::new(translation_key, files.len()));
+
+        let report = Arc::clone(&self.verification_report);
+        *report.lock() = None;
 
         let gd_folder = self.settings.gd_folder.clone();
-        let downloaded_sfx = Arc::clone(&self.downloaded_sfx);
-        let downloaded_music = Arc::clone(&self.downloaded_music);
-        let library_page = self.library_page;
 
         thread::spawn(move || {
-            let ids = match library_page {
-                LibraryPage::Sfx => downloaded_sfx.lock().clone(),
-                LibraryPage::Music => downloaded_music.lock().clone(),
-            };
-            ids.into_iter().try_for_each(|id| {
-                match library_page {
-                    LibraryPage::Sfx =>
-                        if SfxFileEntry::new(id).try_delete_file(&gd_folder).is_ok() {
-                            downloaded_sfx.lock().remove(&id);
-                        },
-                    LibraryPage::Music =>
-                        if MusicFileEntry::new(id).try_delete_file(&gd_folder).is_ok() {
-                            downloaded_music.lock().remove(&id);
-                        },
-                };
+            let checked = files.len();
+            let corrupt = Mutex::new(Vec::new());
 
+            let finished = files.into_par_iter().try_for_each(|file_entry| {
+                if let Err(LibraryError::Corrupt { source, .. }) = file_entry.verify_file(&gd_folder) {
+                    corrupt.lock().push((file_entry.id(), source));
+                }
                 progress.lock().as_mut().map(|progress| progress.finished += 1)
             });
 
+            // stopped tools don't report anything
+            if finished.is_some() {
+                let mut corrupt = corrupt.into_inner();
+                corrupt.sort_by_key(|(id, _)| *id);
+                *report.lock() = Some(VerificationReport { kind, checked, corrupt });
+            }
+
             *progress.lock() = None;
         });
     }
+
+    /// Deletes the corrupt files of the last verification and downloads them again.
+    pub fn redownload_corrupt_files(&self, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
+        let Some(report) = self.verification_report.lock().take() else { return };
+        if report.corrupt.is_empty() { return }
+
+        let ids = report.corrupt.iter().map(|(id, _)| *id);
+
+        match report.kind {
+            FileEntryKind::Sound => {
+                let files = ids.map(|id| sfx_library.file_entry(id)).collect::<Vec<_>>();
+                files.iter().for_each(|&file_entry| self.forget_file(file_entry));
+                self.queue_downloads(files);
+            }
+            FileEntryKind::Song => {
+                let files = ids.map(|id| music_library.file_entry(id)).collect::<Vec<_>>();
+                files.iter().for_each(|&file_entry| self.forget_file(file_entry));
+                self.queue_downloads(files);
+            }
+        }
+    }
+
+    /// Deletes a file along with its cached bytes, which may have been read from the file.
+    fn forget_file(&self, file_entry: impl FileEntry) {
+        match file_entry.kind() {
+            FileEntryKind::Sound => self.sfx_cache.lock(),
+            FileEntryKind::Song => self.music_cache.lock(),
+        }.remove(&file_entry.id());
+
+        self.delete_sound(file_entry);
+    }
 }
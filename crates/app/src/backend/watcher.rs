@@ -0,0 +1,186 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use ahash::HashSet;
+use eframe::egui;
+use itertools::{Either, Itertools};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use library::{parse_file_name, EntryId, FileEntryKind};
+
+use super::AppState;
+
+/// How long the GD folder setting has to stay the same before the folder is scanned,
+/// so that it's not scanned for every intermediate value while the path changes.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Reports files in the GD folder which were added, removed or renamed, e.g. by the game or other tools.
+#[derive(Default)]
+pub struct FolderWatcher {
+    /// The folder which was last set up, even if watching it failed.
+    folder: Option<String>,
+    watcher: Option<RecommendedWatcher>,
+    changes: Option<Receiver<Vec<PathBuf>>>,
+    /// A folder setting which differs from `folder`, and since when it's been the setting.
+    pending: Option<(String, Instant)>,
+}
+
+impl FolderWatcher {
+    fn watch(&mut self, ctx: &egui::Context, folder: &str) {
+        self.folder = Some(folder.to_string());
+        self.watcher = None;
+        self.changes = None;
+
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                let _ = sender.send(event.paths);
+                ctx.request_repaint();
+            }
+        });
+
+        let Ok(mut watcher) = watcher else { return };
+        if watcher.watch(Path::new(folder), RecursiveMode::NonRecursive).is_ok() {
+            self.watcher = Some(watcher);
+            self.changes = Some(receiver);
+        }
+    }
+
+    /// Stops watching without setting up a new watcher, e.g. because `folder` doesn't exist.
+    fn unwatch(&mut self, folder: &str) {
+        self.folder = Some(folder.to_string());
+        self.watcher = None;
+        self.changes = None;
+    }
+
+    /// Whether `folder` has been the setting for at least [`SETTLE_TIME`].
+    /// The first folder is used right away.
+    fn is_settled(&mut self, folder: &str) -> bool {
+        if self.folder.is_none() { return true }
+
+        match &self.pending {
+            Some((pending, since)) if pending == folder => since.elapsed() >= SETTLE_TIME,
+            _ => {
+                self.pending = Some((folder.to_string(), Instant::now()));
+                false
+            }
+        }
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    fn changed_paths(&self) -> Vec<PathBuf> {
+        self.changes.iter()
+            .flat_map(|changes| changes.try_iter())
+            .flatten()
+            .collect()
+    }
+}
+
+fn scan_folder(folder: &str) -> (HashSet<EntryId>, HashSet<EntryId>) {
+    files::read_dir(folder).into_iter().flatten()
+        .flat_map(|file| file.file_name().into_string())
+        .filter_map(|name| parse_file_name(&name))
+        .partition_map(|(kind, id)| match kind {
+            FileEntryKind::Sound => Either::Left(id),
+            FileEntryKind::Song => Either::Right(id),
+        })
+}
+
+impl AppState {
+    /// Watches the GD folder from the settings, rescanning it once the setting stops changing,
+    /// and applies the file changes reported since the last frame.
+    pub fn sync_gd_folder(&mut self, ctx: &egui::Context) {
+        if self.folder_watcher.folder.as_ref() != Some(&self.settings.gd_folder) {
+            if self.folder_watcher.is_settled(&self.settings.gd_folder) {
+                self.folder_watcher.pending = None;
+                self.reset_gd_folder(ctx);
+            } else {
+                ctx.request_repaint_after(SETTLE_TIME);
+            }
+        } else {
+            // changed back before it settled
+            self.folder_watcher.pending = None;
+        }
+
+        for path in self.folder_watcher.changed_paths() {
+            self.update_file(&path);
+        }
+    }
+
+    fn reset_gd_folder(&mut self, ctx: &egui::Context) {
+        if !self.is_gd_folder_valid() {
+            self.folder_watcher.unwatch(&self.settings.gd_folder);
+            self.unlisted_sfx.clear();
+            self.unlisted_music.clear();
+            self.downloaded_sfx.lock().clear();
+            self.downloaded_music.lock().clear();
+            return
+        }
+
+        // watch first so that no change between scanning and watching is missed
+        self.folder_watcher.watch(ctx, &self.settings.gd_folder);
+        self.rescan_gd_folder();
+    }
+
+    fn rescan_gd_folder(&mut self) {
+        let (downloaded_sfx, downloaded_music) = scan_folder(&self.settings.gd_folder);
+
+        self.unlisted_sfx = downloaded_sfx.difference(&self.library_sfx).copied().sorted().collect();
+        self.unlisted_music = downloaded_music.difference(&self.library_music).copied().sorted().collect();
+        *self.downloaded_sfx.lock() = downloaded_sfx;
+        *self.downloaded_music.lock() = downloaded_music;
+    }
+
+    fn update_file(&mut self, path: &Path) {
+        let Some((kind, id)) = path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_file_name)
+        else { return };
+
+        // events can be outdated or merged, so the file system decides
+        let exists = path.is_file();
+
+        let (downloaded, library, unlisted) = match kind {
+            FileEntryKind::Sound => (&self.downloaded_sfx, &self.library_sfx, &mut self.unlisted_sfx),
+            FileEntryKind::Song => (&self.downloaded_music, &self.library_music, &mut self.unlisted_music),
+        };
+
+        match exists {
+            true => downloaded.lock().insert(id),
+            false => downloaded.lock().remove(&id),
+        };
+
+        if library.contains(&id) { return }
+
+        match (exists, unlisted.iter().position(|&unlisted_id| unlisted_id == id)) {
+            (true, None) => unlisted.push(id),
+            (false, Some(position)) => { unlisted.remove(position); }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_settled() {
+        let mut watcher = FolderWatcher::default();
+        assert!(watcher.is_settled("/first"));
+
+        watcher.unwatch("/first");
+        assert!(!watcher.is_settled("/new"));
+        assert!(!watcher.is_settled("/new"));
+        // every change starts over
+        assert!(!watcher.is_settled("/newer"));
+
+        watcher.pending = Some(("/newer".to_string(), Instant::now() - SETTLE_TIME));
+        assert!(watcher.is_settled("/newer"));
+    }
+}
@@ -1,8 +1,12 @@
-use eframe::egui::{Context, SidePanel};
+use eframe::egui::{mutex::Mutex, Button, CollapsingHeader, Context, SidePanel, TextEdit, Ui};
+use once_cell::sync::Lazy;
 
 use library::{MusicLibrary, SfxLibrary};
 
 use crate::{layout, tabs, backend::AppState};
+use crate::backend::smart_collections::SmartCollection;
+
+static SMART_COLLECTION_RESULT: Lazy<Mutex<Option<Result<String, String>>>> = Lazy::new(|| Mutex::new(None));
 
 pub fn render(ctx: &Context, app_state: &mut AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
     SidePanel::left("left_panel")
@@ -11,6 +15,11 @@ pub fn render(ctx: &Context, app_state: &mut AppState, sfx_library: &SfxLibrary,
         .default_width(layout::DEFAULT_LIBRARY_WIDTH)
         .show(ctx, |ui| {
             use tabs::*;
+
+            // collections apply to the search, so they're shown with the tabs which have one
+            if matches!(app_state.selected_tab, Tab::Library | Tab::Favorites) {
+                add_smart_collections(ui, app_state);
+            }
             
             match app_state.selected_tab {
                 Tab::Library => library::render(ui, app_state, sfx_library, music_library),
@@ -22,3 +31,59 @@ pub fn render(ctx: &Context, app_state: &mut AppState, sfx_library: &SfxLibrary,
             }
         });
 }
+
+fn add_smart_collections(ui: &mut Ui, app_state: &mut AppState) {
+    CollapsingHeader::new(t!("smart_collections")).show(ui, |ui| {
+        let mut applied = None;
+        let mut removed = None;
+
+        for collection in app_state.smart_collections.iter() {
+            ui.horizontal(|ui| {
+                if ui.selectable_label(collection.is_applied(app_state), &collection.name).clicked() {
+                    applied = Some(collection.name.clone());
+                }
+                if ui.small_button(t!("smart_collections.remove")).clicked() {
+                    removed = Some(collection.name.clone());
+                }
+            });
+        }
+
+        if let Some(collection) = applied.and_then(|name| app_state.smart_collections.get(&name).cloned()) {
+            collection.apply(app_state);
+        }
+        if let Some(name) = removed {
+            *SMART_COLLECTION_RESULT.lock() = Some(match app_state.smart_collections.remove_collection(&name) {
+                Ok(()) => Ok(t!("smart_collections.remove.success", name = name).to_string()),
+                Err(error) => Err(t!("smart_collections.remove.error", error = format!("{error:#}")).to_string()),
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut app_state.smart_collection_name)
+                .hint_text(t!("smart_collections.name"))
+                .desired_width(120.0));
+
+            let name = app_state.smart_collection_name.trim().to_string();
+            let button = ui.add_enabled(!name.is_empty(), Button::new(t!("smart_collections.save")))
+                .on_hover_text(t!("smart_collections.save.hint"));
+            if button.clicked() {
+                let collection = SmartCollection::from_app_state(name.clone(), app_state);
+                *SMART_COLLECTION_RESULT.lock() = Some(match app_state.smart_collections.save_collection(collection) {
+                    Ok(()) => {
+                        app_state.smart_collection_name.clear();
+                        Ok(t!("smart_collections.save.success", name = name).to_string())
+                    }
+                    Err(error) => Err(t!("smart_collections.save.error", error = format!("{error:#}")).to_string()),
+                });
+            }
+        });
+
+        match &*SMART_COLLECTION_RESULT.lock() {
+            Some(Ok(message)) => { ui.label(message); }
+            Some(Err(message)) => { ui.colored_label(ui.visuals().error_fg_color, message); }
+            None => {}
+        }
+    });
+
+    ui.separator();
+}
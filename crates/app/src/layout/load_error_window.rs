@@ -0,0 +1,97 @@
+use std::error::Error;
+
+use eframe::egui::{Button, CentralPanel, Context, RichText, Spinner, Ui};
+
+use library::LibraryError;
+
+use crate::backend::{loading::LoadFailure, LibraryPage};
+use crate::i18n::LocalizedEnum;
+
+/// Shows why the libraries couldn't be loaded.
+/// Returns `true` once the app should be started with whatever was loaded.
+pub fn render(ctx: &Context, failure: &mut LoadFailure) -> bool {
+    failure.poll_retry();
+
+    if failure.libraries.is_complete() {
+        return true
+    }
+
+    if failure.is_retrying() {
+        ctx.request_repaint();
+    }
+
+    let mut start = false;
+
+    CentralPanel::default().show(ctx, |ui| {
+        ui.heading(t!("startup.load_failed"));
+
+        ui.add_space(10.0);
+
+        add_library_status(ui, LibraryPage::Sfx, failure.libraries.sfx_library.as_ref().err());
+        add_library_status(ui, LibraryPage::Music, failure.libraries.music_library.as_ref().err());
+
+        ui.add_space(10.0);
+
+        ui.label(t!("settings.gd_folder"));
+        ui.code(&failure.settings.gd_folder);
+
+        ui.add_space(10.0);
+
+        ui.horizontal(|ui| {
+            let enabled = !failure.is_retrying();
+
+            if ui.add_enabled(enabled, Button::new(t!("settings.gd_folder.select"))).clicked() {
+                let file_dialog = rfd::FileDialog::new()
+                    .set_directory(&failure.settings.gd_folder);
+
+                if let Some(folder) = file_dialog.pick_folder() {
+                    failure.settings.gd_folder = folder.display().to_string();
+                    failure.settings.try_save_if_changed();
+                    failure.retry();
+                }
+            }
+
+            if ui.add_enabled(enabled, Button::new(t!("startup.retry"))).clicked() {
+                failure.retry();
+            }
+
+            let continue_button = ui.add_enabled(failure.can_continue(), Button::new(t!("startup.continue")));
+            if continue_button.clicked() {
+                start = true;
+            }
+
+            if failure.is_retrying() {
+                ui.add(Spinner::new());
+            }
+        });
+    });
+
+    start
+}
+
+fn add_library_status(ui: &mut Ui, library_page: LibraryPage, error: Option<&LibraryError>) {
+    ui.strong(library_page.localize_variant());
+
+    match error {
+        None => {
+            ui.label(t!("startup.library_loaded"));
+        }
+        Some(error) => {
+            ui.label(RichText::new(format_error_chain(error)).color(ui.visuals().error_fg_color));
+        }
+    }
+
+    ui.add_space(5.0);
+}
+
+fn format_error_chain(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        message.push_str(&format!("\n{}", t!("startup.caused_by", error = error)));
+        source = error.source();
+    }
+
+    message
+}
@@ -1,12 +1,16 @@
 use eframe::{egui::*, epaint::Vec2};
+use itertools::Itertools;
+use pretty_bytes::converter::convert as pretty_bytes;
+use pretty_duration::pretty_duration;
 use strum::IntoEnumIterator;
 
-use library::{music::Song, SfxLibrary};
+use library::{music::Song, MusicLibrary, SfxLibrary};
 use library::sfx::SfxLibraryEntry;
 
 use crate::backend::{AppState, LibraryPage};
+use crate::backend::favorites::{FavoriteKey, DEFAULT_COLLECTION};
 use crate::backend::settings::SelectMode;
-use crate::backend::search::{SearchSettings, SortingMode};
+use crate::backend::search::{SortCriterion, SortKey};
 use crate::i18n::LocalizedEnum;
 use crate::images;
 
@@ -14,6 +18,8 @@ pub mod tabs_panel;
 pub mod left_window;
 pub mod right_window;
 pub mod debug_window;
+pub mod load_error_window;
+pub mod whats_new_window;
 
 pub const MIN_LIBRARY_WIDTH: f32 = 200.0;
 pub const DEFAULT_LIBRARY_WIDTH: f32 = 300.0;
@@ -27,6 +33,29 @@ pub const MIN_SCALE_FACTOR: f32 = 0.7;
 
 pub const FAVORITE_ALPHA: u8 = 100;
 
+pub fn add_category_totals(ui: &mut Ui, library: &SfxLibrary, category: &SfxLibraryEntry) {
+    let totals = library.category_totals(category);
+
+    ui.label(t!("library.category.sounds", sounds = totals.sounds));
+    if totals.categories > 0 {
+        ui.label(t!("library.category.categories", categories = totals.categories));
+    }
+    ui.label(t!("sound.info.size", size = pretty_bytes(totals.bytes as f64)));
+    ui.label(t!("sound.info.duration", duration = pretty_duration(&totals.duration, None)));
+}
+
+pub fn add_category_menu(ui: &mut Ui, app_state: &AppState, library: &SfxLibrary, category: &SfxLibraryEntry) {
+    if !app_state.is_gd_folder_valid() { return }
+
+    if ui.add_enabled(!app_state.is_offline(), Button::new(t!("library.category.download")))
+        .on_disabled_hover_text(t!("settings.offline_mode.unavailable"))
+        .clicked()
+    {
+        app_state.download_category(library, category);
+        ui.close_menu();
+    }
+}
+
 pub fn add_library_page_selection(ui: &mut Ui, app_state: &mut AppState) {
     ui.horizontal(|ui| {
         for page in LibraryPage::iter() {
@@ -36,38 +65,78 @@ pub fn add_library_page_selection(ui: &mut Ui, app_state: &mut AppState) {
     ui.separator();
 }
 
-pub fn add_search_area(ui: &mut Ui, search_settings: &mut SearchSettings) {
+pub fn add_search_area(ui: &mut Ui, app_state: &mut AppState) {
     ui.heading(t!("search"));
 
-    ui.add(TextEdit::singleline(&mut search_settings.search_query).hint_text(t!("search")));
-    
-    ui.horizontal(|ui| {
-        let label = format!("{}: {}", SortingMode::localize_enum(), search_settings.sorting_mode.localize_variant());
-        ui.menu_button(label, |ui| {
-            for mode in SortingMode::iter() {
-                let radio_button = ui.radio_value(
-                    &mut search_settings.sorting_mode,
-                    mode, mode.localize_variant()
-                );
-                if radio_button.clicked() {
-                    ui.close_menu();
-                }
-            }
-        });
+    ui.add(TextEdit::singleline(&mut app_state.search_settings.search_query).hint_text(t!("search")))
+        .on_hover_text(t!("search.syntax"));
+
+    if let Some(error) = app_state.search_results.error(app_state.library_page) {
+        ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+    }
 
-        ui.checkbox(&mut search_settings.show_downloaded, t!("search.show_downloaded"));
+    ui.horizontal(|ui| {
+        add_sort_order(ui, app_state);
+        ui.checkbox(&mut app_state.search_settings.show_downloaded, t!("search.show_downloaded"));
     });
 
     ui.separator();
 }
 
+fn add_sort_order(ui: &mut Ui, app_state: &mut AppState) {
+    let page = app_state.library_page;
+    let sort_order = app_state.settings.sort_order_mut(page);
+
+    let direction = |criterion: &SortCriterion| match criterion.descending {
+        false => t!("search.sort.ascending"),
+        true => t!("search.sort.descending"),
+    };
+    let label = format!(
+        "{}: {}",
+        SortKey::localize_enum(),
+        sort_order.0.iter().map(|criterion| format!("{} {}", criterion.key.localize_variant(), direction(criterion))).join(", "),
+    );
+
+    ui.menu_button(label, |ui| {
+        let can_remove = sort_order.0.len() > 1;
+        let mut removed = None;
+
+        for (index, criterion) in sort_order.0.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let key_label = criterion.key.localize_variant().to_string();
+                ui.menu_button(key_label, |ui| {
+                    let keys = SortKey::iter().filter(|key| page == LibraryPage::Music || !key.is_music_only());
+                    for key in keys {
+                        if ui.radio_value(&mut criterion.key, key, key.localize_variant()).clicked() {
+                            ui.close_menu();
+                        }
+                    }
+                });
+                if ui.button(direction(criterion)).clicked() {
+                    criterion.descending = !criterion.descending;
+                }
+                if ui.add_enabled(can_remove, Button::new(t!("search.sort.remove"))).clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+
+        if let Some(index) = removed {
+            sort_order.0.remove(index);
+        }
+        if ui.button(t!("search.sort.add")).clicked() {
+            sort_order.0.push(SortCriterion::default());
+        }
+    });
+}
+
 pub fn add_sfx_button(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibrary, entry: &SfxLibraryEntry) {
     if !app_state.is_matching_entry(entry, library) {
         return // don't render filtered buttons at all
     }
 
     let image = app_state.favorites
-        .has_favorite(entry.id)
+        .has_favorite(FavoriteKey::sfx(entry.id))
         .then_some(Image::new(images::FAVORITE_STAR).tint(Color32::from_white_alpha(FAVORITE_ALPHA))); // set opacity 0-255
 
     let text = WidgetText::from(&entry.name);
@@ -85,14 +154,15 @@ pub fn add_sfx_button(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibrar
     }
 
     button.context_menu(|ui: &mut Ui| {
-        let favorite_button_label = match app_state.favorites.has_favorite(entry.id) {
+        let favorite_button_label = match app_state.favorites.has_favorite(FavoriteKey::sfx(entry.id)) {
             false => t!("sound.favorite.add"),
             true => t!("sound.favorite.remove"),
         };
         if ui.button(favorite_button_label).clicked() {
-            app_state.favorites.toggle_favorite(entry.id);
+            app_state.favorites.toggle_favorite(FavoriteKey::sfx(entry.id));
             ui.close_menu();
         }
+        add_collections_menu(ui, app_state, FavoriteKey::sfx(entry.id));
 
         if app_state.is_gd_folder_valid() {
             if app_state.is_sfx_downloaded(entry.id) {
@@ -100,7 +170,10 @@ pub fn add_sfx_button(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibrar
                     app_state.delete_sound(entry.into_file_entry());
                     ui.close_menu();
                 }
-            } else if ui.button(t!("sound.download")).clicked() {
+            } else if ui.add_enabled(!app_state.is_offline(), Button::new(t!("sound.download")))
+                .on_disabled_hover_text(t!("settings.offline_mode.unavailable"))
+                .clicked()
+            {
                 app_state.download_sound(entry.into_file_entry());
                 ui.close_menu();
             }
@@ -108,13 +181,32 @@ pub fn add_sfx_button(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibrar
     });
 }
 
-pub fn add_music_button(ui: &mut Ui, app_state: &mut AppState, song: &Song) {
-    if !app_state.is_matching_song(song) {
+fn add_collections_menu(ui: &mut Ui, app_state: &mut AppState, key: FavoriteKey) {
+    if app_state.favorites.collections().len() <= 1 { return }
+
+    ui.menu_button(t!("sound.collections"), |ui| {
+        let mut toggled = None;
+        for (index, collection) in app_state.favorites.collections().iter().enumerate() {
+            if index == DEFAULT_COLLECTION { continue }
+
+            let mut contains = collection.contains(key);
+            if ui.checkbox(&mut contains, &collection.name).clicked() {
+                toggled = Some(index);
+            }
+        }
+        if let Some(index) = toggled {
+            app_state.favorites.toggle_in_collection(index, key);
+        }
+    });
+}
+
+pub fn add_music_button(ui: &mut Ui, app_state: &mut AppState, library: &MusicLibrary, song: &Song) {
+    if !app_state.is_matching_song(song, library) {
         return
     }
 
     let image = app_state.favorites
-    .has_favorite(song.id)
+    .has_favorite(FavoriteKey::song(song.id))
     .then_some(Image::new(images::FAVORITE_STAR).tint(Color32::from_white_alpha(FAVORITE_ALPHA))); // set opacity 0-255
 
     let text = WidgetText::from(&song.name);
@@ -132,14 +224,15 @@ pub fn add_music_button(ui: &mut Ui, app_state: &mut AppState, song: &Song) {
     }
 
     button.context_menu(|ui: &mut Ui| {
-        let favorite_button_label = match app_state.favorites.has_favorite(song.id) {
+        let favorite_button_label = match app_state.favorites.has_favorite(FavoriteKey::song(song.id)) {
             false => t!("sound.favorite.add"),
             true => t!("sound.favorite.remove"),
         };
         if ui.button(favorite_button_label).clicked() {
-            app_state.favorites.toggle_favorite(song.id);
+            app_state.favorites.toggle_favorite(FavoriteKey::song(song.id));
             ui.close_menu();
         }
+        add_collections_menu(ui, app_state, FavoriteKey::song(song.id));
 
         if app_state.is_gd_folder_valid() {
             if app_state.is_music_downloaded(song.id) {
@@ -147,7 +240,10 @@ pub fn add_music_button(ui: &mut Ui, app_state: &mut AppState, song: &Song) {
                     app_state.delete_sound(song.into_file_entry());
                     ui.close_menu();
                 }
-            } else if ui.button(t!("sound.download")).clicked() {
+            } else if ui.add_enabled(!app_state.is_offline(), Button::new(t!("sound.download")))
+                .on_disabled_hover_text(t!("settings.offline_mode.unavailable"))
+                .clicked()
+            {
                 app_state.download_sound(song.into_file_entry());
                 ui.close_menu();
             }
@@ -4,27 +4,34 @@ use eframe::{egui::*, epaint::Color32};
 use pretty_bytes::converter::convert as pretty_bytes;
 
 use audio::AudioSettings;
-use library::{BytesSize, EntryId, FileEntry, MusicFileEntry, SfxFileEntry};
+use library::{BytesSize, EntryId, FileEntry, MusicLibrary, SfxLibrary};
+use library::music::Song;
 
 use crate::images;
 use crate::backend::{AppState, LibraryPage};
+use crate::backend::favorites::FavoriteKey;
 
 // TODO can we make this less of a list of ui elements
 // and instead maybe put some stuff on the right side of the screen
 // also make sure everything fits on the ui
-pub fn render(ctx: &Context, app_state: &mut AppState) {
+pub fn render(ctx: &Context, app_state: &mut AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
     CentralPanel::default().show(ctx, |ui| {
         match app_state.library_page {
-            LibraryPage::Sfx => render_sfx_window(ui, app_state),
-            LibraryPage::Music => render_music_window(ui, app_state),
+            LibraryPage::Sfx => render_sfx_window(ui, app_state, sfx_library),
+            LibraryPage::Music => render_music_window(ui, app_state, music_library),
         }
     });
 }
 
-fn render_sfx_window(ui: &mut Ui, app_state: &mut AppState) {
+fn render_sfx_window(ui: &mut Ui, app_state: &mut AppState, sfx_library: &SfxLibrary) {
     let Some(entry) = &app_state.selected_sfx else { return };
 
-    let entry_id = entry.id;
+    let file_entry = entry.into_file_entry();
+
+    let category_path = sfx_library.path_string(entry.parent_id);
+    if !category_path.is_empty() {
+        ui.weak(category_path);
+    }
 
     ui.heading(&entry.name);
 
@@ -44,7 +51,7 @@ fn render_sfx_window(ui: &mut Ui, app_state: &mut AppState) {
 
     ui.add_space(25.0);
 
-    render_buttons(ui, app_state, entry_id, SfxFileEntry::new(entry_id), app_state.is_sfx_downloaded(entry_id));
+    render_buttons(ui, app_state, file_entry, app_state.is_sfx_downloaded(file_entry.id()));
 
     ui.add_space(10.0);
 
@@ -64,6 +71,34 @@ fn render_sound_info(ui: &mut Ui, id: EntryId, bytes: BytesSize, duration: Durat
     }
 }
 
+fn render_song_details(ui: &mut Ui, song: &Song, music_library: &MusicLibrary) {
+    let artists = song.artist_ids()
+        .map(|id| music_library.credits.get(&id).map(|credit| credit.name.clone()).unwrap_or_else(|| id.to_string()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    ui.label(t!("sound.info.artists", artists = artists));
+
+    if let Some(priority) = song.priority {
+        ui.label(t!("sound.info.priority", priority = priority));
+    }
+    if let Some(new_type) = song.new_type {
+        ui.label(t!("sound.info.new_type", new_type = new_type));
+    }
+
+    ui.horizontal(|ui| {
+        if song.ncs {
+            ui.label(t!("sound.info.ncs"));
+        }
+        if song.new {
+            ui.label(t!("sound.info.new"));
+        }
+    });
+
+    if let Some(link) = &song.external_link {
+        ui.hyperlink_to(t!("sound.info.external_link"), link);
+    }
+}
+
 const IMAGE_BUTTON_SIZE: Vec2 = Vec2::new(32.0, 32.0);
 
 macro_rules! image_button {
@@ -83,10 +118,10 @@ macro_rules! image_button {
     };
 }
 
-fn render_music_window(ui: &mut Ui, app_state: &mut AppState) {
+fn render_music_window(ui: &mut Ui, app_state: &mut AppState, music_library: &MusicLibrary) {
     let Some(song) = &app_state.selected_music else { return };
 
-    let song_id = song.id;
+    let file_entry = song.into_file_entry();
 
     ui.heading(&song.name);
 
@@ -97,17 +132,18 @@ fn render_music_window(ui: &mut Ui, app_state: &mut AppState) {
     ui.add_space(10.0);
 
     render_sound_info(ui, song.id, song.bytes, song.duration, 0);
+    render_song_details(ui, song, music_library);
 
     ui.add_space(25.0);
 
-    render_buttons(ui, app_state, song_id, MusicFileEntry::new(song_id), app_state.is_music_downloaded(song_id));
+    render_buttons(ui, app_state, file_entry, app_state.is_music_downloaded(file_entry.id()));
 
     ui.add_space(10.0);
 
     render_audio_settings(ui, app_state);
 }
 
-fn render_buttons(ui: &mut Ui, app_state: &mut AppState, id: EntryId, file_entry: impl FileEntry + 'static, is_downloaded: bool) {
+fn render_buttons(ui: &mut Ui, app_state: &mut AppState, file_entry: impl FileEntry + 'static, is_downloaded: bool) {
     ui.horizontal(|ui| {
         if image_button!(
             ui,
@@ -136,8 +172,14 @@ fn render_buttons(ui: &mut Ui, app_state: &mut AppState, id: EntryId, file_entry
                 ui,
                 images::DOWNLOAD,
                 IMAGE_BUTTON_SIZE,
-                !is_downloaded,
-            ).on_hover_text(t!("sound.download")).clicked() {
+                !is_downloaded && !app_state.is_offline(),
+            )
+            .on_hover_text(t!("sound.download"))
+            .on_disabled_hover_text(match app_state.is_offline() {
+                true => t!("settings.offline_mode.unavailable"),
+                false => t!("sound.download"),
+            })
+            .clicked() {
                 app_state.download_sound(file_entry);
             }
 
@@ -156,17 +198,19 @@ fn render_buttons(ui: &mut Ui, app_state: &mut AppState, id: EntryId, file_entry
 
     ui.add_space(5.0);
 
+    let favorite_key = FavoriteKey::of(&file_entry);
+
     ui.horizontal(|ui| {
         if image_button!(
             ui,
-            match app_state.favorites.has_favorite(id) {
+            match app_state.favorites.has_favorite(favorite_key) {
                 true => images::STAR_SOLID,
                 false => images::STAR_REGULAR,
             },
             IMAGE_BUTTON_SIZE,
             true,
-        ).on_hover_text(t!(format!("sound.favorite.{}", if app_state.favorites.has_favorite(id) { "remove" } else { "add" }))).clicked() {
-            app_state.favorites.toggle_favorite(id);
+        ).on_hover_text(t!(format!("sound.favorite.{}", if app_state.favorites.has_favorite(favorite_key) { "remove" } else { "add" }))).clicked() {
+            app_state.favorites.toggle_favorite(favorite_key);
             ui.close_menu();
         }
 
@@ -0,0 +1,106 @@
+use std::fmt::Display;
+
+use eframe::egui::{CollapsingHeader, Context, ScrollArea, Ui, Window};
+use pretty_bytes::converter::convert as pretty_bytes;
+
+use library::{EntryId, SfxLibrary};
+use library::diff::{Change, MusicLibraryDiff, SfxLibraryDiff};
+
+use crate::backend::{AppState, LibraryPage};
+use crate::backend::loading::LibraryUpdates;
+use crate::i18n::LocalizedEnum;
+
+/// Shows what changed in the libraries that were updated during startup.
+pub fn render(ctx: &Context, app_state: &mut AppState, sfx_library: &SfxLibrary) {
+    if app_state.library_updates.is_empty() { return }
+
+    let mut open = true;
+
+    Window::new(t!("whats_new"))
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(400.0)
+        .show(ctx, |ui| {
+            ScrollArea::vertical().show(ui, |ui| {
+                if let Some(diff) = &app_state.library_updates.sfx {
+                    render_sfx_diff(ui, diff, sfx_library);
+                }
+                if let Some(diff) = &app_state.library_updates.music {
+                    render_music_diff(ui, diff);
+                }
+            });
+        });
+
+    if !open {
+        app_state.library_updates = LibraryUpdates::default();
+    }
+}
+
+fn render_sfx_diff(ui: &mut Ui, diff: &SfxLibraryDiff, sfx_library: &SfxLibrary) {
+    ui.heading(LibraryPage::Sfx.localize_variant());
+    ui.label(t!("whats_new.version", old = diff.old_version, new = diff.new_version));
+
+    let category_name = |id: EntryId| sfx_library.entries.get(&id)
+        .map(|entry| entry.name.clone())
+        .unwrap_or_else(|| id.to_string());
+
+    add_section(ui, t!("whats_new.added"), "sfx_added", &diff.added, |entry| {
+        format!("{} ({}) → {}", entry.name, entry.id, category_name(entry.parent_id))
+    });
+    add_section(ui, t!("whats_new.removed"), "sfx_removed", &diff.removed, |entry| {
+        format!("{} ({})", entry.name, entry.id)
+    });
+    add_section(ui, t!("whats_new.renamed"), "sfx_renamed", &diff.renamed, |change| {
+        format_change(change, |name| name.clone())
+    });
+    add_section(ui, t!("whats_new.moved"), "sfx_moved", &diff.moved, |change| {
+        format_change(change, |&id| category_name(id))
+    });
+    add_section(ui, t!("whats_new.resized"), "sfx_resized", &diff.resized, |change| {
+        format_change(change, |&bytes| pretty_bytes(bytes as f64))
+    });
+
+    ui.separator();
+}
+
+fn render_music_diff(ui: &mut Ui, diff: &MusicLibraryDiff) {
+    ui.heading(LibraryPage::Music.localize_variant());
+    ui.label(t!("whats_new.version", old = diff.old_version, new = diff.new_version));
+
+    add_section(ui, t!("whats_new.added"), "music_added", &diff.added, |song| {
+        format!("{} ({})", song.name, song.id)
+    });
+    add_section(ui, t!("whats_new.removed"), "music_removed", &diff.removed, |song| {
+        format!("{} ({})", song.name, song.id)
+    });
+    add_section(ui, t!("whats_new.renamed"), "music_renamed", &diff.renamed, |change| {
+        format_change(change, |name| name.clone())
+    });
+    add_section(ui, t!("whats_new.resized"), "music_resized", &diff.resized, |change| {
+        format_change(change, |&bytes| pretty_bytes(bytes as f64))
+    });
+    add_section(ui, t!("whats_new.new_tags"), "music_new_tags", &diff.new_tags, |tag| {
+        tag.name.clone()
+    });
+    add_section(ui, t!("whats_new.new_artists"), "music_new_artists", &diff.new_artists, |credit| {
+        credit.name.clone()
+    });
+
+    ui.separator();
+}
+
+fn add_section<T>(ui: &mut Ui, title: impl Display, id_salt: &str, items: &[T], format: impl Fn(&T) -> String) {
+    if items.is_empty() { return }
+
+    CollapsingHeader::new(format!("{title} ({})", items.len()))
+        .id_salt(id_salt)
+        .show(ui, |ui| {
+            for item in items {
+                ui.label(format(item));
+            }
+        });
+}
+
+fn format_change<T>(change: &Change<T>, format: impl Fn(&T) -> String) -> String {
+    format!("{}: {} → {}", change.id, format(&change.old), format(&change.new))
+}
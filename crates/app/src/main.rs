@@ -1,11 +1,11 @@
-use std::{thread, sync::Arc};
+use std::sync::Arc;
 
 use eframe::{egui, HardwareAcceleration, NativeOptions};
 use egui::{IconData, ViewportBuilder};
 
 use library::{MusicLibrary, SfxLibrary};
 
-use crate::backend::{AppState, settings::PersistentSettings};
+use crate::backend::{AppState, loading::{Libraries, LibraryUpdates, LoadFailure}, settings::PersistentSettings};
 
 #[macro_use]
 extern crate rust_i18n;
@@ -24,6 +24,7 @@ files::get_build_output!(include!("i18n.rs"));
 const ICON_BYTES: &[u8] = files::get_build_output!(include_bytes!("icon.bin"));
 
 pub struct GdSfx {
+    load_failure: Option<LoadFailure>,
     app_state: AppState,
     sfx_library: SfxLibrary,
     music_library: MusicLibrary,
@@ -38,25 +39,30 @@ impl GdSfx {
         let settings = PersistentSettings::load();
         rust_i18n::set_locale(&settings.locale);
 
-        let gd_folder = settings.gd_folder.clone();
-
-        thread::scope(|scope| {
-            let sfx_library_handle = scope.spawn(||
-                SfxLibrary::load(&gd_folder)
-                    .expect("TODO: info screen with error message and retry button")
-            );
-
-            let music_library_handle = scope.spawn(||
-                MusicLibrary::load(&gd_folder)
-                    .expect("TODO: info screen with error message and retry button")
-            );
-
-            let sfx_library = sfx_library_handle.join().unwrap();
-            let music_library = music_library_handle.join().unwrap();
-            let app_state = AppState::load(settings, &sfx_library, &music_library);
+        match Libraries::load(&settings) {
+            Libraries { sfx_library: Ok(sfx_library), music_library: Ok(music_library), updates } =>
+                Self::start(settings, sfx_library, music_library, updates),
+
+            libraries => Self {
+                load_failure: Some(LoadFailure::new(settings, libraries)),
+                app_state: AppState::default(),
+                sfx_library: SfxLibrary::default(),
+                music_library: MusicLibrary::default(),
+            },
+        }
+    }
 
-            Self { app_state, sfx_library, music_library }
-        })
+    fn start(
+        settings: PersistentSettings,
+        sfx_library: SfxLibrary,
+        music_library: MusicLibrary,
+        library_updates: LibraryUpdates,
+    ) -> Self {
+        let content_source = settings.create_content_source();
+        let mut app_state = AppState::load(settings, content_source, &sfx_library, &music_library);
+        app_state.library_updates = library_updates;
+
+        Self { load_failure: None, app_state, sfx_library, music_library }
     }
 }
 
@@ -64,12 +70,21 @@ impl eframe::App for GdSfx {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         use layout::*;
 
+        if let Some(load_failure) = &mut self.load_failure {
+            if !load_error_window::render(ctx, load_failure) { return }
+
+            let load_failure = self.load_failure.take().expect("load failure should still be present");
+            let (settings, sfx_library, music_library, library_updates) = load_failure.into_parts();
+            *self = Self::start(settings, sfx_library, music_library, library_updates);
+        }
+
         backend::update(ctx, &mut self.app_state);
 
         tabs_panel::render(ctx, &mut self.app_state);
         left_window::render(ctx, &mut self.app_state, &self.sfx_library, &self.music_library);
-        right_window::render(ctx, &mut self.app_state);
+        right_window::render(ctx, &mut self.app_state, &self.sfx_library, &self.music_library);
         debug_window::render(ctx, &mut self.app_state);
+        whats_new_window::render(ctx, &mut self.app_state, &self.sfx_library);
 
         backend::request_optional_repaint(ctx, &mut self.app_state);
     }
@@ -1,36 +1,229 @@
-use eframe::egui::{ScrollArea, Ui};
+use std::borrow::Cow;
 
-use library::{MusicLibrary, SfxLibrary};
+use ahash::HashSet;
+
+use eframe::egui::{Button, CollapsingHeader, ComboBox, ScrollArea, TextEdit, Ui};
+use eframe::egui::mutex::Mutex;
+use once_cell::sync::Lazy;
+use strum::IntoEnumIterator;
+
+use library::{FileEntryKind, MusicLibrary, SfxLibrary};
 
 use crate::{layout, backend::{AppState, LibraryPage}};
+use crate::backend::favorites::{FavoriteCollection, FavoriteItem, FavoriteKey, ImportMode, ImportReport, SharedItem, DEFAULT_COLLECTION};
+use crate::i18n::LocalizedEnum;
+
+static SHARING_RESULT: Lazy<Mutex<Option<Result<String, String>>>> = Lazy::new(|| Mutex::new(None));
+static IMPORT_REPORT: Lazy<Mutex<Option<ImportReport>>> = Lazy::new(|| Mutex::new(None));
 
 pub fn render(ui: &mut Ui, app_state: &mut AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
     layout::add_library_page_selection(ui, app_state);
-    layout::add_search_area(ui, &mut app_state.search_settings);
+    render_collection_selection(ui, app_state);
+    render_sharing(ui, app_state, sfx_library, music_library);
+    layout::add_search_area(ui, app_state);
+
+    let collection = app_state.favorites_collection;
+    let kind = match app_state.library_page {
+        LibraryPage::Sfx => FileEntryKind::Sound,
+        LibraryPage::Music => FileEntryKind::Song,
+    };
+    let items: Vec<FavoriteItem> = app_state.favorites.collections()[collection].items.iter()
+        .filter(|item| item.key.kind == kind)
+        .cloned()
+        .collect();
 
     ScrollArea::vertical().auto_shrink(false).show(ui, |ui| {
         match app_state.library_page {
             LibraryPage::Sfx => {
-                let mut sounds = sfx_library.iter_sounds().collect::<Vec<_>>();
-                sounds.sort_by(|&a, &b| app_state.search_settings.sorting_mode.compare_entries(a, b));
-            
-                for sound in sounds {
-                    if app_state.favorites.has_favorite(sound.id) {
-                        layout::add_sfx_button(ui, app_state, sfx_library, sound);
-                    }
+                let mut sounds: Vec<_> = items.iter()
+                    .filter_map(|item| Some((item, sfx_library.get_entry(item.key.id)?)))
+                    .filter(|(_, sound)| app_state.is_matching_entry(sound, sfx_library))
+                    .collect();
+                if !app_state.favorites_collection_order {
+                    sounds.sort_by(|(_, a), (_, b)| app_state.settings.sfx_sort_order.compare_entries(*a, *b));
+                }
+                let shown = sounds.iter().map(|(item, _)| item.key).collect();
+
+                for (item, sound) in sounds {
+                    render_item(ui, app_state, item, &shown, |ui, app_state| layout::add_sfx_button(ui, app_state, sfx_library, sound));
                 }
             }
 
             LibraryPage::Music => {
-                let mut songs = music_library.songs.values().collect::<Vec<_>>();
-                songs.sort_by(|&a, &b| app_state.search_settings.sorting_mode.compare_entries(a, b));
+                let mut songs: Vec<_> = items.iter()
+                    .filter_map(|item| Some((item, music_library.songs.get(&item.key.id)?)))
+                    .filter(|(_, song)| app_state.is_matching_song(song, music_library))
+                    .collect();
+                if !app_state.favorites_collection_order {
+                    songs.sort_by(|(_, a), (_, b)| app_state.settings.music_sort_order.compare_songs(a, b, music_library));
+                }
+                let shown = songs.iter().map(|(item, _)| item.key).collect();
+
+                for (item, song) in songs {
+                    render_item(ui, app_state, item, &shown, |ui, app_state| layout::add_music_button(ui, app_state, music_library, song));
+                }
+            }
+        }
+    });
+}
+
+fn collection_name(index: usize, collection: &FavoriteCollection) -> Cow<'_, str> {
+    match index {
+        DEFAULT_COLLECTION => t!("tab.favorites"),
+        _ => Cow::Borrowed(&collection.name),
+    }
+}
+
+fn render_collection_selection(ui: &mut Ui, app_state: &mut AppState) {
+    if app_state.favorites_collection >= app_state.favorites.collections().len() {
+        app_state.favorites_collection = DEFAULT_COLLECTION;
+    }
+
+    ui.horizontal(|ui| {
+        let selected = app_state.favorites_collection;
+        let collections = app_state.favorites.collections();
+        ComboBox::from_id_salt("favorites_collection")
+            .selected_text(collection_name(selected, &collections[selected]))
+            .show_ui(ui, |ui| {
+                for (index, collection) in collections.iter().enumerate() {
+                    ui.selectable_value(&mut app_state.favorites_collection, index, collection_name(index, collection));
+                }
+            });
+
+        if ui.add_enabled(selected != DEFAULT_COLLECTION, Button::new(t!("favorites.collection.remove"))).clicked() {
+            app_state.favorites.remove_collection(selected);
+            app_state.favorites_collection = DEFAULT_COLLECTION;
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.add(TextEdit::singleline(&mut app_state.favorites_collection_name)
+            .hint_text(t!("favorites.collection.name"))
+            .desired_width(120.0));
+
+        let name = app_state.favorites_collection_name.trim().to_string();
+        if ui.add_enabled(!name.is_empty(), Button::new(t!("favorites.collection.add"))).clicked() {
+            if let Some(index) = app_state.favorites.add_collection(&name) {
+                app_state.favorites_collection = index;
+                app_state.favorites_collection_name.clear();
+            }
+        }
+    });
+
+    ui.checkbox(&mut app_state.favorites_collection_order, t!("favorites.collection_order"));
+
+    ui.separator();
+}
 
-                for song in songs {
-                    if app_state.favorites.has_favorite(song.id) {
-                        layout::add_music_button(ui, app_state, song);
-                    }
+fn render_sharing(ui: &mut Ui, app_state: &mut AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
+    CollapsingHeader::new(t!("favorites.sharing")).show(ui, |ui| {
+        ui.horizontal(|ui| {
+            if ui.button(t!("favorites.export")).clicked() {
+                let file_dialog = rfd::FileDialog::new()
+                    .set_file_name("favorites.json")
+                    .add_filter("JSON", &["json"])
+                    .add_filter(t!("favorites.sharing.text"), &["txt"]);
+
+                if let Some(path) = file_dialog.save_file() {
+                    *IMPORT_REPORT.lock() = None;
+                    *SHARING_RESULT.lock() = Some(match app_state.favorites.export(&path, sfx_library, music_library) {
+                        Ok(()) => Ok(t!("favorites.export.success", path = path.display()).to_string()),
+                        Err(error) => Err(format!("{error:#}")),
+                    });
+                }
+            }
+
+            if ui.button(t!("favorites.import")).clicked() {
+                let file_dialog = rfd::FileDialog::new()
+                    .add_filter(t!("favorites.sharing.files"), &["json", "txt"]);
+
+                if let Some(path) = file_dialog.pick_file() {
+                    let result = app_state.favorites.import(&path, app_state.favorites_import_mode, sfx_library, music_library);
+                    *SHARING_RESULT.lock() = Some(match &result {
+                        Ok(report) => Ok(t!("favorites.import.success", count = report.imported).to_string()),
+                        Err(error) => Err(format!("{error:#}")),
+                    });
+                    *IMPORT_REPORT.lock() = result.ok();
                 }
             }
+
+            for mode in ImportMode::iter() {
+                ui.radio_value(&mut app_state.favorites_import_mode, mode, mode.localize_variant());
+            }
+        });
+
+        match &*SHARING_RESULT.lock() {
+            Some(Ok(message)) => { ui.label(message); }
+            Some(Err(message)) => { ui.colored_label(ui.visuals().error_fg_color, message); }
+            None => {}
+        }
+
+        if let Some(report) = &*IMPORT_REPORT.lock() {
+            render_import_report(ui, report);
+        }
+    });
+
+    ui.separator();
+}
+
+fn render_import_report(ui: &mut Ui, report: &ImportReport) {
+    let describe = |item: &SharedItem| format!("{} {} \"{}\"", t!(format!("favorites.kind.{}", match item.kind {
+        FileEntryKind::Sound => "sound",
+        FileEntryKind::Song => "song",
+    })), item.id, item.name);
+
+    if !report.missing.is_empty() {
+        CollapsingHeader::new(t!("favorites.import.missing", count = report.missing.len())).show(ui, |ui| {
+            for item in &report.missing {
+                ui.label(describe(item));
+            }
+        });
+    }
+
+    if !report.renamed.is_empty() {
+        CollapsingHeader::new(t!("favorites.import.renamed", count = report.renamed.len())).show(ui, |ui| {
+            for (item, name) in &report.renamed {
+                ui.label(format!("{} → \"{name}\"", describe(item)));
+            }
+        });
+    }
+}
+
+/// `shown` are the keys of all items listed in the tab.
+fn render_item(
+    ui: &mut Ui,
+    app_state: &mut AppState,
+    item: &FavoriteItem,
+    shown: &HashSet<FavoriteKey>,
+    add_button: impl FnOnce(&mut Ui, &mut AppState),
+) {
+    let collection = app_state.favorites_collection;
+    let is_shown = |key| shown.contains(&key);
+
+    ui.horizontal(|ui| {
+        if app_state.favorites_collection_order {
+            if ui.small_button("⬆").clicked() {
+                app_state.favorites.move_item(collection, item.key, true, is_shown);
+            }
+            if ui.small_button("⬇").clicked() {
+                app_state.favorites.move_item(collection, item.key, false, is_shown);
+            }
+        }
+
+        add_button(ui, app_state);
+
+        let mut note = match &app_state.favorite_note {
+            Some((key, note)) if *key == item.key => note.clone(),
+            _ => item.note.clone(),
+        };
+        let response = ui.add(TextEdit::singleline(&mut note).hint_text(t!("favorites.note")));
+        if response.changed() {
+            app_state.favorite_note = Some((item.key, note));
+        }
+        if response.lost_focus() {
+            if let Some((key, note)) = app_state.favorite_note.take() {
+                app_state.favorites.set_note(collection, key, note);
+            }
         }
     });
 }
@@ -1,25 +1,25 @@
 use std::time::Duration;
 
 use eframe::egui::mutex::Mutex;
-use eframe::egui::{CollapsingHeader, ComboBox, ScrollArea, Ui};
+use eframe::egui::{Button, CollapsingHeader, ComboBox, ScrollArea, Ui};
 use egui_infinite_scroll::InfiniteScroll;
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use strum::IntoEnumIterator;
 
-use library::{EntryId, MusicLibrary, SfxLibrary};
+use library::{MusicLibrary, SfxLibrary};
 use library::music::Song;
 use library::sfx::{SfxLibraryEntry, EntryKind};
 
-use crate::backend::{AppState, LibraryPage, search::{ListedMode, MusicFilters}, settings::SearchFilterMode};
+use crate::backend::{AppState, LibraryPage, UNLISTED_ID, search::ListedMode, settings::SearchFilterMode};
 use crate::i18n::LocalizedEnum;
 use crate::layout;
 
-const UNLISTED_ID: EntryId = EntryId::MAX;
 
 pub fn render(ui: &mut Ui, app_state: &mut AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
     layout::add_library_page_selection(ui, app_state);
-    layout::add_search_area(ui, &mut app_state.search_settings);
+    layout::add_search_area(ui, app_state);
+    add_download_results_button(ui, app_state, sfx_library, music_library);
 
     match app_state.library_page {
         LibraryPage::Sfx => render_sfx_library(ui, app_state, sfx_library),
@@ -27,6 +27,18 @@ pub fn render(ui: &mut Ui, app_state: &mut AppState, sfx_library: &SfxLibrary, m
     }
 }
 
+fn add_download_results_button(ui: &mut Ui, app_state: &AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
+    if !app_state.is_gd_folder_valid() { return }
+
+    let button = ui.add_enabled(!app_state.is_offline(), Button::new(t!("library.download_results")))
+        .on_hover_text(t!("library.download_results.hint"))
+        .on_disabled_hover_text(t!("settings.offline_mode.unavailable"));
+
+    if button.triple_clicked() {
+        app_state.download_search_results(sfx_library, music_library);
+    }
+}
+
 fn render_sfx_library(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibrary) {
     let collapse_all = ui.button(t!("library.collapse_all")).clicked();
 
@@ -46,6 +58,7 @@ fn render_sfx_library(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibrar
                     bytes: 0,
                     duration: Duration::ZERO,
                 },
+                extra_fields: Vec::new(),
             })
             .filter(|entry| app_state.is_matching_entry(entry, library))
             .collect();
@@ -59,7 +72,7 @@ fn render_sfx_library(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibrar
             CollapsingHeader::new(t!("library.unlisted_sfx"))
                 .open((!enabled || collapse_all).then_some(false))
                 .show(ui, |ui| {
-                    unlisted_sounds.sort_by(|a, b| app_state.search_settings.sorting_mode.compare_entries(a, b));
+                    unlisted_sounds.sort_by(|a, b| app_state.settings.sfx_sort_order.compare_entries(a, b));
                     for entry in unlisted_sounds {
                         layout::add_sfx_button(ui, app_state, library, &entry);
                     }
@@ -71,7 +84,7 @@ fn render_sfx_library(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibrar
 }
 
 fn render_sfx_recursive(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibrary, mut entries: Vec<&SfxLibraryEntry>, collapse_all: bool) {
-    entries.sort_by(|&a, &b| app_state.search_settings.sorting_mode.compare_entries(a, b));
+    entries.sort_by(|&a, &b| app_state.settings.sfx_sort_order.compare_entries(a, b));
     for entry in entries {
         match entry.kind {
             EntryKind::Category => {
@@ -84,6 +97,9 @@ fn render_sfx_recursive(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibr
                         .show(ui, |ui| {
                             render_sfx_recursive(ui, app_state, library, library.iter_children(entry).collect(), collapse_all);
                         })
+                        .header_response
+                        .on_hover_ui(|ui| layout::add_category_totals(ui, library, entry))
+                        .context_menu(|ui| layout::add_category_menu(ui, app_state, library, entry));
                 });
             }
             EntryKind::Sound { .. } => layout::add_sfx_button(ui, app_state, library, entry),
@@ -91,7 +107,9 @@ fn render_sfx_recursive(ui: &mut Ui, app_state: &mut AppState, library: &SfxLibr
     }
 }
 
-static INFINITE_SCROLL_MUSIC: Lazy<Mutex<(Vec<Song>, InfiniteScroll<Song, usize>)>> = Lazy::new(|| Mutex::new((vec![], InfiniteScroll::new())));
+type SongScroll = (Vec<Song>, InfiniteScroll<Song, usize>);
+
+static INFINITE_SCROLL_MUSIC: Lazy<Mutex<SongScroll>> = Lazy::new(|| Mutex::new((vec![], InfiniteScroll::new())));
 
 fn render_music_library(ui: &mut Ui, app_state: &mut AppState, library: &MusicLibrary) {
     music_filters(ui, app_state, library);
@@ -101,14 +119,17 @@ fn render_music_library(ui: &mut Ui, app_state: &mut AppState, library: &MusicLi
             ListedMode::Listed => {
                 let mut songs: Vec<Song> = library.songs
                     .values()
+                    .filter(|song| app_state.music_filters.matches(song) && app_state.is_matching_song(song, library))
                     .cloned()
-                    .filter(|song| {
-                        let MusicFilters { tags, artists, .. } = &app_state.music_filters;
-                        tags.iter().all(|tag| song.tags.contains(tag)) && (artists.is_empty() || artists.contains(&song.credit_id))
-                    }).collect();
+                    .collect();
 
                 let songs_count = songs.len();
-                songs.sort_by(|a, b| app_state.search_settings.sorting_mode.compare_entries(a, b));
+                songs.sort_by(|a, b| app_state.settings.music_sort_order.compare_songs(a, b, library));
+                if app_state.search_results.is_active() {
+                    // best matches first, the sort order breaks ties
+                    let results = &app_state.search_results;
+                    songs.sort_by(|a, b| results.music_score(b.id).total_cmp(&results.music_score(a.id)));
+                }
 
                 let mut infinite_scroll = INFINITE_SCROLL_MUSIC.lock();
                 if infinite_scroll.0 != songs {
@@ -116,34 +137,19 @@ fn render_music_library(ui: &mut Ui, app_state: &mut AppState, library: &MusicLi
                     infinite_scroll.1 = InfiniteScroll::new().end_loader(move |cursor, callback| {
                         let start = cursor.unwrap_or(0).min(songs.len());
                         let end = (start + 100).min(songs.len());
-                        callback(Ok((songs[start..end].iter().cloned().collect(), Some(end))))
+                        callback(Ok((songs[start..end].to_vec(), Some(end))))
                     });
                 }
                 infinite_scroll.1.ui(ui, songs_count, |ui, _i, song| {
-                    layout::add_music_button(ui, app_state, song);
+                    layout::add_music_button(ui, app_state, library, song);
                 });
             },
             ListedMode::Unlisted => {
-                let mut songs: Vec<_> = app_state.unlisted_music.iter()
-                    .map(|&id| Song {
-                        id,
-                        name: id.to_string(),
-                        bytes: 0,
-                        credit_id: UNLISTED_ID,
-                        duration: Duration::ZERO,
-                        tags: Vec::new(),
-                        ncs: false,
-                        unk2: String::new(),
-                        url: String::new(),
-                        new: false,
-                        unk4: String::new(),
-                        unk5: String::new(),
-                    })
-                    .collect();
-                songs.sort_by(|a, b| app_state.search_settings.sorting_mode.compare_entries(a, b));
+                let mut songs: Vec<_> = app_state.unlisted_songs().collect();
+                songs.sort_by(|a, b| app_state.settings.music_sort_order.compare_songs(a, b, library));
 
                 for song in songs {
-                    layout::add_music_button(ui, app_state, &song);
+                    layout::add_music_button(ui, app_state, library, &song);
                 }
             }
         }
@@ -179,17 +185,21 @@ fn music_filters(ui: &mut Ui, app_state: &mut AppState, library: &MusicLibrary)
 fn music_listed_filters(ui: &mut Ui, app_state: &mut AppState, library: &MusicLibrary) {
     let available_songs = library.songs.values()
         .filter(|song| app_state.music_filters.tags.is_empty() || app_state.music_filters.tags.iter().all(|tag| song.tags.contains(tag)))
+        .filter(|song| !app_state.music_filters.only_new || song.new)
         .filter(|song| !app_state.search_settings.show_downloaded || app_state.is_music_downloaded(song.id))
         .collect::<Vec<_>>();
 
     let available_artists = available_songs.iter()
-        .map(|song| song.credit_id)
+        .flat_map(|song| song.artist_ids())
         .unique()
         .flat_map(|id| library.credits.get(&id))
         .sorted_unstable_by_key(|credit| &credit.name);
 
     let available_tags = available_songs.iter()
-        .filter(|song| app_state.music_filters.artists.is_empty() || app_state.music_filters.artists.contains(&song.credit_id))
+        .filter(|song| {
+            let artists = &app_state.music_filters.artists;
+            artists.is_empty() || song.artist_ids().any(|id| artists.contains(&id))
+        })
         .flat_map(|song| &song.tags)
         .unique()
         .flat_map(|id| library.tags.get(id))
@@ -231,9 +241,12 @@ fn music_listed_filters(ui: &mut Ui, app_state: &mut AppState, library: &MusicLi
                     }
                 });
 
+            ui.checkbox(&mut app_state.music_filters.only_new, t!("library.filters.only_new"));
+
             if ui.button("Reset filters").clicked() {
                 app_state.music_filters.tags.clear();
                 app_state.music_filters.artists.clear();
+                app_state.music_filters.only_new = false;
             }
         });
     });
@@ -23,6 +23,20 @@ pub fn render(ui: &mut Ui, app_state: &mut AppState) {
 
     select_gd_folder(ui, app_state);
 
+    ui.add_space(10.0);
+
+    set_content_url(ui, app_state);
+
+    if ui.checkbox(&mut app_state.settings.offline_mode, t!("settings.offline_mode")).changed() {
+        app_state.update_content_source();
+    }
+    ui.label(RichText::new(t!("settings.offline_mode.hint")).small());
+
+    ui.add_space(10.0);
+
+    ui.add(Slider::new(&mut app_state.settings.download_concurrency, 1..=16).text(t!("settings.download_concurrency")));
+    ui.add(Slider::new(&mut app_state.settings.download_retries, 0..=10).text(t!("settings.download_retries")));
+
     reset_settings(ui, app_state);
 
     app_state.settings.try_save_if_changed();
@@ -70,9 +84,22 @@ fn select_gd_folder(ui: &mut Ui, app_state: &mut AppState) {
 
     ui.add_enabled(false, text_edit);
 
+    if !is_invalid && !app_state.folder_watcher.is_watching() {
+        ui.colored_label(ui.visuals().warn_fg_color, t!("settings.gd_folder.not_watched"));
+    }
+
+    // running downloads keep writing to the folder they were started with
+    let disabled_reason = if app_state.is_tool_running() {
+        Some(t!("settings.cannot_modify.tool_running"))
+    } else if app_state.download_queue.is_active() {
+        Some(t!("settings.cannot_modify.downloading"))
+    } else {
+        None
+    };
+
     let button = Button::new(t!("settings.gd_folder.select"));
-    let response = ui.add_enabled(!app_state.is_tool_running(), button)
-        .on_disabled_hover_text(t!("settings.cannot_modify.tool_running"));
+    let response = ui.add_enabled(disabled_reason.is_none(), button)
+        .on_disabled_hover_text(disabled_reason.unwrap_or_default());
     
     if response.clicked() {
         let file_dialog = rfd::FileDialog::new()
@@ -84,12 +111,37 @@ fn select_gd_folder(ui: &mut Ui, app_state: &mut AppState) {
     }
 }
 
+fn set_content_url(ui: &mut Ui, app_state: &mut AppState) {
+    ui.label(t!("settings.content_url"));
+
+    let disabled_reason = if app_state.is_offline() {
+        Some(t!("settings.offline_mode.unavailable"))
+    } else if app_state.is_tool_running() {
+        Some(t!("settings.cannot_modify.tool_running"))
+    } else if app_state.download_queue.is_active() {
+        Some(t!("settings.cannot_modify.downloading"))
+    } else {
+        None
+    };
+    let text_edit = TextEdit::singleline(&mut app_state.settings.content_url)
+        .desired_width(f32::INFINITY)
+        .hint_text(t!("settings.content_url.hint"));
+
+    let response = ui.add_enabled(disabled_reason.is_none(), text_edit)
+        .on_disabled_hover_text(disabled_reason.unwrap_or_default());
+
+    if response.lost_focus() {
+        app_state.update_content_source();
+    }
+}
+
 fn reset_settings(ui: &mut Ui, app_state: &mut AppState) {
     ui.with_layout(Layout::bottom_up(Align::Min), |ui| {
         ui.add_space(4.0);
 
         if layout::add_caution_button(ui, t!("settings.reset")).triple_clicked() {
             app_state.settings = PersistentSettings::default();
+            app_state.update_content_source();
         }
         
         ui.label(t!("settings.reset.instruction"));
@@ -1,8 +1,9 @@
-use eframe::egui::Ui;
+use eframe::egui::{CollapsingHeader, Ui};
 use pretty_bytes::converter::convert as pretty_bytes;
 use pretty_duration::pretty_duration;
 
-use library::{MusicLibrary, SfxLibrary};
+use library::{EntryId, MusicLibrary, SfxLibrary};
+use library::validate::ValidationReport;
 
 use crate::{backend::{AppState, LibraryPage}, i18n::LocalizedEnum, layout};
 
@@ -37,6 +38,22 @@ pub fn render(ui: &mut Ui, app_state: &mut AppState, sfx_library: &SfxLibrary, m
 
     ui.label(t!("stats.library.version", version = matcher!(sfx_library.get_version().to_string(), music_library.version.to_string())));
 
+    ui.label(t!("stats.library.dropped_records", records = matcher!(sfx_library.dropped_records(), music_library.dropped_records())));
+
+    if app_state.library_page == LibraryPage::Sfx && !app_state.sfx_validation.is_valid() {
+        render_validation_report(ui, &app_state.sfx_validation);
+    }
+
+    let warnings = matcher!(&sfx_library.warnings, &music_library.warnings);
+    if !warnings.is_empty() {
+        CollapsingHeader::new(t!("stats.library.warnings", warnings = warnings.len()))
+            .show(ui, |ui| {
+                for warning in warnings {
+                    ui.colored_label(ui.visuals().warn_fg_color, warning.to_string());
+                }
+            });
+    }
+
     ui.add_space(20.0);
 
     ui.heading(t!(format!("stats.files.{}", app_state.library_page.localization_key())));
@@ -47,3 +64,22 @@ pub fn render(ui: &mut Ui, app_state: &mut AppState, sfx_library: &SfxLibrary, m
 
     ui.label(t!("stats.sounds.unlisted", sounds = matcher!(app_state.unlisted_sfx.len(), app_state.unlisted_music.len())));
 }
+
+fn render_validation_report(ui: &mut Ui, report: &ValidationReport) {
+    fn add_ids(ui: &mut Ui, key: &str, ids: &[EntryId]) {
+        if ids.is_empty() { return }
+        let ids = ids.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        ui.colored_label(ui.visuals().warn_fg_color, t!(key, ids = ids));
+    }
+
+    CollapsingHeader::new(t!("stats.library.tree_problems", problems = report.problem_count()))
+        .show(ui, |ui| {
+            add_ids(ui, "stats.library.tree_problems.orphans", &report.orphans);
+            add_ids(ui, "stats.library.tree_problems.sound_parents", &report.sound_parents);
+            for cycle in &report.cycles {
+                add_ids(ui, "stats.library.tree_problems.cycle", cycle);
+            }
+            add_ids(ui, "stats.library.tree_problems.duplicate_ids", &report.duplicate_ids);
+            add_ids(ui, "stats.library.tree_problems.unreachable", &report.unreachable);
+        });
+}
@@ -1,11 +1,19 @@
-use eframe::{egui::{mutex::Mutex, Context, Layout, Modal, Sides, Slider, Ui}, emath::Align};
+use eframe::{egui::{mutex::Mutex, Button, Context, Layout, Modal, ProgressBar, ScrollArea, Sides, Slider, TextStyle, Ui}, emath::Align};
 use once_cell::sync::Lazy;
+use strum::IntoEnumIterator;
 
-use library::{FileEntry, MusicFileEntry, MusicLibrary, SfxFileEntry, SfxLibrary};
+use library::{EntryId, FileEntryKind, LibraryError, MusicLibrary, SfxLibrary};
+use library::export::ExportFormat;
+use library::music::Song;
+use library::sfx::SfxLibraryEntry;
 
-use crate::{backend::{AppState, LibraryPage}, i18n::LocalizedEnum, layout};
+use crate::{backend::{AppState, LibraryPage, downloads::DownloadStatus}, i18n::LocalizedEnum, layout};
+use crate::backend::tools::{entry_name, ItemResult, ToolReport};
 
 static DOWNLOAD_MODAL_OPEN: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
+static SHOWN_DOWNLOAD_STATUS: Lazy<Mutex<DownloadStatus>> = Lazy::new(|| Mutex::new(DownloadStatus::Failed));
+static REPORT_EXPORT_RESULT: Lazy<Mutex<Option<Result<String, String>>>> = Lazy::new(|| Mutex::new(None));
+static EXPORT_RESULT: Lazy<Mutex<Option<Result<String, String>>>> = Lazy::new(|| Mutex::new(None));
 
 pub fn render(ui: &mut Ui, ctx: &Context, app_state: &mut AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
     layout::add_library_page_selection(ui, app_state);
@@ -20,24 +28,23 @@ pub fn render(ui: &mut Ui, ctx: &Context, app_state: &mut AppState, sfx_library:
 
     let is_tool_running = app_state.is_tool_running();
     if *DOWNLOAD_MODAL_OPEN.lock() {
-        download_range_select_modal(ctx, app_state);
+        download_range_select_modal(ctx, app_state, sfx_library, music_library);
     }
 
-    ui.add_enabled_ui(!is_tool_running, |ui| {
+    if app_state.is_offline() {
+        ui.colored_label(ui.visuals().warn_fg_color, t!("settings.offline_mode.unavailable"));
+        ui.add_space(10.0);
+    }
+
+    ui.add_enabled_ui(!is_tool_running && !app_state.is_offline(), |ui| {
         let download_all_key = format!("tools.download_all.{}", app_state.library_page.localization_key());
         
         if ui.button(t!(&download_all_key)).triple_clicked() {
             match app_state.library_page {
                 LibraryPage::Sfx =>
-                    app_state.download_multiple_sfx(
-                        download_all_key,
-                        sfx_library.sound_ids().iter().map(|&i| SfxFileEntry::new(i)).collect(),
-                    ),
+                    app_state.queue_downloads(sfx_library.iter_sounds().map(SfxLibraryEntry::into_file_entry)),
                 LibraryPage::Music =>
-                    app_state.download_multiple_sfx(
-                        download_all_key,
-                        music_library.songs.keys().map(|&i| MusicFileEntry::new(i)).collect(),
-                    ),
+                    app_state.queue_downloads(music_library.songs.values().map(Song::into_file_entry)),
             }
         }
         if ui.button(t!("tools.download_from_range")).clicked() {
@@ -47,13 +54,242 @@ pub fn render(ui: &mut Ui, ctx: &Context, app_state: &mut AppState, sfx_library:
 
     ui.add_space(10.0);
 
-    ui.add_enabled_ui(!is_tool_running, |ui| {
+    // queued downloads would write the files again
+    ui.add_enabled_ui(!is_tool_running && !app_state.download_queue.is_active(), |ui| {
         let delete_all_key = format!("tools.delete_all.{}", app_state.library_page.localization_key());
 
         if ui.button(t!(&delete_all_key)).triple_clicked() {
             app_state.delete_all_sfx(delete_all_key);
         }
     });
+
+    render_tool_report(ui, app_state, sfx_library, music_library);
+
+    ui.add_space(10.0);
+
+    render_verification(ui, app_state, sfx_library, music_library);
+
+    ui.add_space(10.0);
+
+    render_download_queue(ui, app_state, sfx_library, music_library);
+
+    ui.add_space(10.0);
+
+    render_export(ui, app_state, sfx_library, music_library);
+}
+
+fn render_export(ui: &mut Ui, app_state: &AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
+    ui.horizontal(|ui| {
+        for &format in ExportFormat::ALL {
+            if !ui.button(t!("tools.export", format = format.name())).clicked() { continue }
+
+            let file_name = format!("{}.{}", app_state.library_page.localization_key(), format.extension());
+            let file_dialog = rfd::FileDialog::new()
+                .set_file_name(file_name)
+                .add_filter(format.name(), &[format.extension()]);
+
+            let Some(path) = file_dialog.save_file() else { continue };
+
+            let result = match app_state.library_page {
+                LibraryPage::Sfx => sfx_library.export(format, &path),
+                LibraryPage::Music => music_library.export(format, &path),
+            };
+
+            *EXPORT_RESULT.lock() = Some(match result {
+                Ok(()) => Ok(t!("tools.export.success", path = path.display()).to_string()),
+                Err(error) => Err(format_export_error(&error)),
+            });
+        }
+    });
+
+    match &*EXPORT_RESULT.lock() {
+        Some(Ok(message)) => { ui.label(message); }
+        Some(Err(message)) => { ui.colored_label(ui.visuals().error_fg_color, message); }
+        None => {}
+    }
+}
+
+fn render_download_queue(ui: &mut Ui, app_state: &AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
+    ui.label(t!("tools.downloads"));
+
+    let queue = &app_state.download_queue;
+    let counts = queue.counts();
+
+    if counts.total() == 0 {
+        ui.weak(t!("tools.downloads.empty"));
+        return
+    }
+
+    let finished = counts.finished();
+    let progress = finished as f32 / counts.total() as f32;
+    ui.add(ProgressBar::new(progress).text(format!("{finished}/{}", counts.total())));
+
+    if counts.queued + counts.downloading == 0 {
+        render_report_summary(ui, &queue.report(), sfx_library, music_library);
+    }
+
+    let mut shown_status = SHOWN_DOWNLOAD_STATUS.lock();
+
+    ui.horizontal(|ui| {
+        for status in DownloadStatus::iter() {
+            let text = format!("{} ({})", status.localize_variant(), counts.get(status));
+            ui.selectable_value(&mut *shown_status, status, text);
+        }
+    });
+
+    ui.horizontal(|ui| {
+        let is_paused = queue.is_paused();
+        let pause_key = if is_paused { "tools.downloads.resume" } else { "tools.downloads.pause" };
+        if ui.button(t!(pause_key)).clicked() {
+            queue.set_paused(!is_paused);
+            if is_paused {
+                app_state.start_downloads();
+            }
+        }
+
+        if ui.add_enabled(counts.failed > 0, Button::new(t!("tools.downloads.retry_failed"))).clicked() {
+            app_state.retry_failed_downloads();
+        }
+
+        if ui.add_enabled(counts.done > 0, Button::new(t!("tools.downloads.clear_finished"))).clicked() {
+            queue.clear_finished();
+        }
+
+        let can_cancel = counts.queued + counts.downloading > 0;
+        if ui.add_enabled(can_cancel, Button::new(t!("tools.downloads.cancel"))).triple_clicked() {
+            queue.cancel();
+        }
+    });
+
+    let items = queue.items(*shown_status);
+    let row_height = ui.text_style_height(&TextStyle::Body);
+
+    ScrollArea::vertical().id_salt("download_queue").max_height(150.0).show_rows(ui, row_height, items.len(), |ui, rows| {
+        for item in &items[rows] {
+            let label = entry_label(item.kind, item.id, sfx_library, music_library);
+            match &item.error {
+                Some(error) => ui.label(format!("{label}: {error}")),
+                None => ui.label(label),
+            };
+        }
+    });
+}
+
+/// Name and ID of a sound or song, for lists of files.
+fn entry_label(kind: FileEntryKind, id: EntryId, sfx_library: &SfxLibrary, music_library: &MusicLibrary) -> String {
+    match entry_name(kind, id, sfx_library, music_library) {
+        Some(name) => format!("{name} ({id})"),
+        None => id.to_string(),
+    }
+}
+
+fn render_tool_report(ui: &mut Ui, app_state: &AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
+    let report_guard = app_state.tool_report.lock();
+    let Some(report) = &*report_guard else { return };
+
+    ui.label(t!(&report.translation_key));
+    render_report_summary(ui, report, sfx_library, music_library);
+
+    let failed = report.failed().collect::<Vec<_>>();
+    let row_height = ui.text_style_height(&TextStyle::Body);
+
+    ScrollArea::vertical().id_salt("failed_items").max_height(150.0).show_rows(ui, row_height, failed.len(), |ui, rows| {
+        for item in &failed[rows] {
+            let label = entry_label(item.kind, item.id, sfx_library, music_library);
+            ui.label(format!("{label}: {}", item.error.as_deref().unwrap_or_default()));
+        }
+    });
+
+    let can_retry = !failed.is_empty() && !app_state.is_tool_running();
+    let retry_button = ui.add_enabled(can_retry, Button::new(t!("tools.report.retry_failed")));
+    drop(report_guard);
+
+    if retry_button.clicked() {
+        app_state.retry_failed_deletions();
+    }
+}
+
+/// Counts of each result, and a button to export the whole report.
+fn render_report_summary(ui: &mut Ui, report: &ToolReport, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
+    ui.horizontal(|ui| {
+        ui.label(t!(
+            "tools.report.summary",
+            succeeded = report.count(ItemResult::Succeeded),
+            skipped = report.count(ItemResult::Skipped),
+            failed = report.count(ItemResult::Failed),
+        ));
+
+        if !ui.button(t!("tools.report.export")).clicked() { return }
+
+        let file_dialog = rfd::FileDialog::new()
+            .set_file_name("report.csv")
+            .add_filter("CSV", &["csv"]);
+
+        let Some(path) = file_dialog.save_file() else { return };
+
+        *REPORT_EXPORT_RESULT.lock() = Some(match report.export(&path, sfx_library, music_library) {
+            Ok(()) => Ok(t!("tools.export.success", path = path.display()).to_string()),
+            Err(error) => Err(format!("{error:#}")),
+        });
+    });
+
+    match &*REPORT_EXPORT_RESULT.lock() {
+        Some(Ok(message)) => { ui.label(message); }
+        Some(Err(message)) => { ui.colored_label(ui.visuals().error_fg_color, message); }
+        None => {}
+    }
+}
+
+fn render_verification(ui: &mut Ui, app_state: &AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
+    let is_tool_running = app_state.is_tool_running();
+
+    ui.add_enabled_ui(!is_tool_running && app_state.is_gd_folder_valid(), |ui| {
+        let verify_key = format!("tools.verify.{}", app_state.library_page.localization_key());
+
+        if ui.button(t!(&verify_key)).triple_clicked() {
+            let ids = app_state.downloaded_ids();
+            match app_state.library_page {
+                LibraryPage::Sfx =>
+                    app_state.verify_files(verify_key, ids.into_iter().map(|id| sfx_library.file_entry(id)).collect()),
+                LibraryPage::Music =>
+                    app_state.verify_files(verify_key, ids.into_iter().map(|id| music_library.file_entry(id)).collect()),
+            }
+        }
+    });
+
+    let report_guard = app_state.verification_report.lock();
+    let Some(report) = &*report_guard else { return };
+
+    if report.corrupt.is_empty() {
+        ui.label(t!("tools.verify.ok", count = report.checked));
+        return
+    }
+
+    ui.colored_label(
+        ui.visuals().warn_fg_color,
+        t!("tools.verify.corrupt", count = report.corrupt.len(), total = report.checked),
+    );
+
+    ScrollArea::vertical().id_salt("corrupt_files").max_height(150.0).show(ui, |ui| {
+        for (id, error) in &report.corrupt {
+            ui.label(format!("{}: {error}", entry_label(report.kind, *id, sfx_library, music_library)));
+        }
+    });
+
+    let can_redownload = !is_tool_running && !app_state.is_offline();
+    let redownload_button = ui.add_enabled(can_redownload, Button::new(t!("tools.verify.redownload")));
+    drop(report_guard);
+
+    if redownload_button.triple_clicked() {
+        app_state.redownload_corrupt_files(sfx_library, music_library);
+    }
+}
+
+fn format_export_error(error: &LibraryError) -> String {
+    match std::error::Error::source(error) {
+        Some(source) => format!("{error}: {source}"),
+        None => error.to_string(),
+    }
 }
 
 fn render_running_tool(ui: &mut Ui, app_state: &mut AppState) {
@@ -71,7 +307,7 @@ fn render_running_tool(ui: &mut Ui, app_state: &mut AppState) {
     }
 }
 
-fn download_range_select_modal(ctx: &Context, app_state: &mut AppState) {
+fn download_range_select_modal(ctx: &Context, app_state: &mut AppState, sfx_library: &SfxLibrary, music_library: &MusicLibrary) {
     let modal = Modal::new("download_range_select".into()).show(ctx, |ui| {
         ui.heading(t!("tools.download_from_range"));
 
@@ -99,21 +335,14 @@ fn download_range_select_modal(ctx: &Context, app_state: &mut AppState) {
             |_ui| {},
             |ui| {
                 if ui.button(t!("tools.confirm")).triple_clicked() {
-                    let download_from_range_string = "tools.download_from_range".to_string();
                     match app_state.library_page {
                         LibraryPage::Sfx => {
                             let range = app_state.download_id_range_sfx;
-                            app_state.download_multiple_sfx(
-                                download_from_range_string,
-                                (range.0..=range.1).map(SfxFileEntry::new).collect()
-                            );
+                            app_state.queue_range_downloads((range.0..=range.1).map(|id| sfx_library.file_entry(id)));
                         },
                         LibraryPage::Music => {
                             let range = app_state.download_id_range_music;
-                            app_state.download_multiple_sfx(
-                                download_from_range_string,
-                                (range.0..=range.1).map(MusicFileEntry::new).collect()
-                            );
+                            app_state.queue_range_downloads((range.0..=range.1).map(|id| music_library.file_entry(id)));
                         }
                     }
                     *DOWNLOAD_MODAL_OPEN.lock() = false;
@@ -0,0 +1,309 @@
+use std::{collections::BTreeSet, ops::RangeInclusive, path::PathBuf};
+
+use anyhow::{Context as _, Result};
+use rayon::prelude::*;
+use serde_json::{json, Value};
+
+use library::{ContentSource, EntryId, FileEntry, FileEntryKind, MusicLibrary, SfxLibrary};
+use library::music::Song;
+use library::sfx::SfxLibraryEntry;
+
+use crate::Command;
+
+pub struct Context {
+    pub gd_folder: PathBuf,
+    pub source: Box<dyn ContentSource>,
+    pub json: bool,
+}
+
+/// A single printable result, in both output formats.
+struct Item {
+    json: Value,
+    text: String,
+}
+
+enum Status {
+    Downloaded,
+    Deleted,
+    AlreadyPresent,
+    NotPresent,
+    Failed(String),
+}
+
+impl Status {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Downloaded => "downloaded",
+            Self::Deleted => "deleted",
+            Self::AlreadyPresent => "already_present",
+            Self::NotPresent => "not_present",
+            Self::Failed(_) => "failed",
+        }
+    }
+}
+
+pub fn run_sfx(ctx: &Context, command: Command) -> Result<bool> {
+    let library = SfxLibrary::load(&ctx.gd_folder, &*ctx.source)?;
+
+    let matches = |entry: &&SfxLibraryEntry, query: &str| {
+        entry.name.to_lowercase().contains(query) || entry.id.to_string() == query
+    };
+
+    match command {
+        Command::List { downloaded } => {
+            print_list(ctx, sorted_sounds(&library)
+                .filter(|entry| !downloaded || is_downloaded(ctx, entry.into_file_entry()))
+                .map(|entry| sfx_item(ctx, &library, entry)));
+        }
+        Command::Search { query } => {
+            let query = query.to_lowercase();
+            print_list(ctx, sorted_sounds(&library)
+                .filter(|entry| matches(entry, &query))
+                .map(|entry| sfx_item(ctx, &library, entry)));
+        }
+        Command::Download { ids } => {
+            let ids = select_ids(&ids, library.sound_ids().iter().copied());
+            return Ok(transfer(ctx, ids, |id| library.file_entry(id), download))
+        }
+        Command::Delete { ids } => {
+            let candidates = library.sound_ids().iter().copied().chain(downloaded_ids(ctx, FileEntryKind::Sound));
+            return Ok(transfer(ctx, select_ids(&ids, candidates), |id| library.file_entry(id), delete))
+        }
+        Command::Info { id } => {
+            let entry = library.entries().get(&id)
+                .with_context(|| format!("No SFX library entry with ID {id}"))?;
+            print_single(ctx, sfx_item(ctx, &library, entry));
+        }
+    }
+
+    Ok(true)
+}
+
+pub fn run_music(ctx: &Context, command: Command) -> Result<bool> {
+    let library = MusicLibrary::load(&ctx.gd_folder, &*ctx.source)?;
+
+    let matches = |song: &&Song, query: &str| {
+        song.name.to_lowercase().contains(query) || song.id.to_string() == query
+    };
+
+    match command {
+        Command::List { downloaded } => {
+            print_list(ctx, sorted_songs(&library)
+                .filter(|song| !downloaded || is_downloaded(ctx, song.into_file_entry()))
+                .map(|song| song_item(ctx, &library, song)));
+        }
+        Command::Search { query } => {
+            let query = query.to_lowercase();
+            print_list(ctx, sorted_songs(&library)
+                .filter(|song| matches(song, &query))
+                .map(|song| song_item(ctx, &library, song)));
+        }
+        Command::Download { ids } => {
+            let ids = select_ids(&ids, library.songs.keys().copied());
+            return Ok(transfer(ctx, ids, |id| library.file_entry(id), download))
+        }
+        Command::Delete { ids } => {
+            let candidates = library.songs.keys().copied().chain(downloaded_ids(ctx, FileEntryKind::Song));
+            return Ok(transfer(ctx, select_ids(&ids, candidates), |id| library.file_entry(id), delete))
+        }
+        Command::Info { id } => {
+            let song = library.songs.get(&id)
+                .with_context(|| format!("No song with ID {id}"))?;
+            print_single(ctx, song_item(ctx, &library, song));
+        }
+    }
+
+    Ok(true)
+}
+
+fn sorted_sounds(library: &SfxLibrary) -> impl Iterator<Item = &SfxLibraryEntry> {
+    let mut sounds: Vec<_> = library.iter_sounds().collect();
+    sounds.sort_by_key(|entry| entry.id);
+    sounds.into_iter()
+}
+
+fn sorted_songs(library: &MusicLibrary) -> impl Iterator<Item = &Song> {
+    let mut songs: Vec<_> = library.songs.values().collect();
+    songs.sort_by_key(|song| song.id);
+    songs.into_iter()
+}
+
+/// IDs of the files of `kind` in the GD folder, including ones which aren't part of the library.
+fn downloaded_ids(ctx: &Context, kind: FileEntryKind) -> impl Iterator<Item = EntryId> + '_ {
+    files::read_dir(&ctx.gd_folder).into_iter().flatten()
+        .flat_map(|file| file.file_name().into_string())
+        .filter_map(|name| library::parse_file_name(&name))
+        .filter(move |&(file_kind, _)| file_kind == kind)
+        .map(|(_, id)| id)
+}
+
+/// The `candidates` within any of `ranges`, sorted and without duplicates.
+/// Ranges aren't expanded, so that huge ranges don't need any memory.
+fn select_ids(ranges: &[RangeInclusive<EntryId>], candidates: impl IntoIterator<Item = EntryId>) -> Vec<EntryId> {
+    candidates.into_iter()
+        .filter(|id| ranges.iter().any(|range| range.contains(id)))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+fn is_downloaded(ctx: &Context, file_entry: impl FileEntry) -> bool {
+    file_entry.file_exists(&ctx.gd_folder)
+}
+
+fn sfx_item(ctx: &Context, library: &SfxLibrary, entry: &SfxLibraryEntry) -> Item {
+    let downloaded = is_downloaded(ctx, entry.into_file_entry());
+    let category = library.entries().get(&entry.parent_id).map(|parent| parent.name.as_str());
+    let duration = entry.duration().map(|duration| duration.as_secs_f32());
+
+    Item {
+        json: json!({
+            "id": entry.id,
+            "name": entry.name,
+            "parent_id": entry.parent_id,
+            "category": category,
+            "path": library.path_string(entry.id),
+            "is_category": entry.bytes().is_none(),
+            "bytes": entry.bytes(),
+            "duration_seconds": duration,
+            "downloaded": downloaded,
+            "raw": entry.to_string(),
+        }),
+        text: format!(
+            "{}\t{}\t{}\t{:.2}s{}",
+            entry.id,
+            entry.name,
+            entry.bytes().unwrap_or(0),
+            duration.unwrap_or(0.0),
+            if downloaded { "\t(downloaded)" } else { "" },
+        ),
+    }
+}
+
+fn song_item(ctx: &Context, library: &MusicLibrary, song: &Song) -> Item {
+    let downloaded = is_downloaded(ctx, song.into_file_entry());
+    let artist = library.credits.get(&song.credit_id).map(|credit| credit.name.as_str());
+    let tags: Vec<&str> = song.tags.iter()
+        .flat_map(|id| library.tags.get(id))
+        .map(|tag| tag.name.as_str())
+        .collect();
+
+    Item {
+        json: json!({
+            "id": song.id,
+            "name": song.name,
+            "artist_id": song.credit_id,
+            "artist": artist,
+            "extra_artist_ids": song.extra_artist_ids,
+            "bytes": song.bytes,
+            "duration_seconds": song.duration.as_secs(),
+            "tags": tags,
+            "ncs": song.ncs,
+            "new": song.new,
+            "priority": song.priority,
+            "new_type": song.new_type,
+            "external_link": song.external_link,
+            "downloaded": downloaded,
+            "raw": song.to_string(),
+        }),
+        text: format!(
+            "{}\t{}\t{}\t{}\t{}s{}",
+            song.id,
+            song.name,
+            artist.unwrap_or_default(),
+            song.bytes,
+            song.duration.as_secs(),
+            if downloaded { "\t(downloaded)" } else { "" },
+        ),
+    }
+}
+
+fn print_list(ctx: &Context, items: impl Iterator<Item = Item>) {
+    if ctx.json {
+        let values: Vec<Value> = items.map(|item| item.json).collect();
+        println!("{}", Value::Array(values));
+    } else {
+        items.for_each(|item| println!("{}", item.text));
+    }
+}
+
+fn print_single(ctx: &Context, item: Item) {
+    if ctx.json {
+        println!("{}", item.json);
+    } else {
+        println!("{}", item.text);
+    }
+}
+
+fn download(ctx: &Context, file_entry: impl FileEntry) -> Status {
+    if file_entry.file_exists(&ctx.gd_folder) {
+        return Status::AlreadyPresent
+    }
+
+    let result = file_entry.try_download_bytes(&*ctx.source)
+        .and_then(|bytes| file_entry.try_write_bytes(&ctx.gd_folder, bytes));
+
+    match result {
+        Ok(()) => Status::Downloaded,
+        Err(err) => Status::Failed(format!("{:#}", anyhow::Error::new(err))),
+    }
+}
+
+fn delete(ctx: &Context, file_entry: impl FileEntry) -> Status {
+    if !file_entry.file_exists(&ctx.gd_folder) {
+        return Status::NotPresent
+    }
+
+    match file_entry.try_delete_file(&ctx.gd_folder) {
+        Ok(()) => Status::Deleted,
+        Err(err) => Status::Failed(format!("{:#}", anyhow::Error::new(err))),
+    }
+}
+
+/// Runs `action` on every ID in parallel and prints one result per ID.
+/// `file_entry` looks up the entry of an ID, so that downloads are checked against the size from the library.
+/// Returns whether every action succeeded.
+fn transfer<F: FileEntry>(
+    ctx: &Context,
+    ids: Vec<EntryId>,
+    file_entry: impl Fn(EntryId) -> F + Sync,
+    action: fn(&Context, F) -> Status,
+) -> bool {
+    let results: Vec<(EntryId, Status)> = ids.into_par_iter()
+        .map(|id| (id, action(ctx, file_entry(id))))
+        .collect();
+
+    let success = results.iter().all(|(_, status)| !matches!(status, Status::Failed(_)));
+
+    print_list(ctx, results.into_iter().map(|(id, status)| {
+        let error = match &status {
+            Status::Failed(err) => Some(err.clone()),
+            _ => None,
+        };
+        Item {
+            json: json!({ "id": id, "status": status.name(), "error": error }),
+            text: match error {
+                Some(err) => format!("{id}\t{}\t{err}", status.name()),
+                None => format!("{id}\t{}", status.name()),
+            },
+        }
+    }));
+
+    success
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_select_ids() {
+        let candidates = [4460, 4451, 10, 4451, 4000];
+
+        assert_eq!(select_ids(&[4451..=4451], candidates), [4451]);
+        assert_eq!(select_ids(&[4000..=4455, 4450..=4500], candidates), [4000, 4451, 4460]);
+        assert_eq!(select_ids(&[5000..=6000], candidates), [] as [EntryId; 0]);
+        // huge ranges only cost as much as the candidates
+        assert_eq!(select_ids(&[0..=EntryId::MAX], candidates), [10, 4000, 4451, 4460]);
+    }
+}
@@ -0,0 +1,143 @@
+use std::{ops::RangeInclusive, path::PathBuf, process::ExitCode};
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use library::{ContentSource, EntryId, HttpSource, LocalSource, OfflineSource};
+
+mod commands;
+
+#[derive(Parser)]
+#[command(name = "gdsfx", version, about = "Headless access to the Geometry Dash SFX and music libraries")]
+struct Cli {
+    /// Geometry Dash folder containing the library files and downloaded sounds.
+    /// Defaults to the folder detected for the current platform.
+    #[arg(long, global = true)]
+    gd_folder: Option<PathBuf>,
+
+    /// Which library to operate on.
+    #[arg(long, short, global = true, value_enum, default_value_t = LibraryKind::Sfx)]
+    library: LibraryKind,
+
+    /// Print machine-readable JSON instead of plain text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Download from a mirror serving `sfx/` and `music/` under this URL instead of the official servers.
+    #[arg(long, global = true, conflicts_with = "local_source")]
+    mirror: Option<String>,
+
+    /// Read library and sound files from a local directory containing `sfx/` and `music/`.
+    #[arg(long, global = true)]
+    local_source: Option<PathBuf>,
+
+    /// Never access the network; only use local library files and downloaded sounds.
+    #[arg(long, global = true, conflicts_with_all = ["mirror", "local_source"])]
+    offline: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LibraryKind {
+    Sfx,
+    Music,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List every sound or song in the library.
+    List {
+        /// Only list files that are already downloaded.
+        #[arg(long)]
+        downloaded: bool,
+    },
+    /// Search the library by name or exact ID.
+    Search {
+        query: String,
+    },
+    /// Download files by ID or inclusive ID range (e.g. `4451 4000-4100`).
+    /// IDs which aren't part of the library are left out.
+    Download {
+        #[arg(required = true, value_parser = parse_id_range)]
+        ids: Vec<RangeInclusive<EntryId>>,
+    },
+    /// Delete downloaded files by ID or inclusive ID range.
+    /// IDs which are neither part of the library nor downloaded are left out.
+    Delete {
+        #[arg(required = true, value_parser = parse_id_range)]
+        ids: Vec<RangeInclusive<EntryId>>,
+    },
+    /// Show all known information about a sound or song.
+    Info {
+        id: EntryId,
+    },
+}
+
+fn parse_id_range(string: &str) -> Result<RangeInclusive<EntryId>, String> {
+    let parse = |s: &str| s.trim().parse::<EntryId>().map_err(|_| format!("invalid ID \"{s}\""));
+
+    match string.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse(start)?, parse(end)?);
+            if start > end {
+                return Err(format!("range start {start} is greater than range end {end}"))
+            }
+            Ok(start..=end)
+        }
+        None => parse(string).map(|id| id..=id),
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli) {
+        Ok(success) => if success { ExitCode::SUCCESS } else { ExitCode::FAILURE },
+        Err(err) => {
+            eprintln!("error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Returns `Ok(false)` if the command ran but some of its items failed.
+fn run(cli: Cli) -> Result<bool> {
+    let gd_folder = cli.gd_folder
+        .or_else(|| files::paths::GEOMETRY_DASH_DIR.clone())
+        .context("No Geometry Dash folder found, specify one with --gd-folder")?;
+
+    let source: Box<dyn ContentSource> = match (cli.mirror, cli.local_source) {
+        _ if cli.offline => Box::new(OfflineSource),
+        (Some(url), _) => Box::new(HttpSource::with_base_url(&url)?),
+        (None, Some(directory)) => Box::new(LocalSource::new(directory)),
+        (None, None) => Box::new(HttpSource::official()),
+    };
+
+    let ctx = commands::Context { gd_folder, source, json: cli.json };
+
+    match cli.library {
+        LibraryKind::Sfx => commands::run_sfx(&ctx, cli.command),
+        LibraryKind::Music => commands::run_music(&ctx, cli.command),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_id_range() {
+        assert_eq!(parse_id_range("4451"), Ok(4451..=4451));
+        assert_eq!(parse_id_range("4000-4100"), Ok(4000..=4100));
+        assert_eq!(parse_id_range(" 4000 - 4100 "), Ok(4000..=4100));
+        assert_eq!(parse_id_range("0-4294967295"), Ok(0..=EntryId::MAX));
+
+        assert!(parse_id_range("4100-4000").is_err());
+        assert!(parse_id_range("").is_err());
+        assert!(parse_id_range("4000-").is_err());
+        assert!(parse_id_range("4294967296").is_err());
+        assert!(parse_id_range("0-4294967296").is_err());
+    }
+}
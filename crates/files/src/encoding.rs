@@ -1,37 +1,144 @@
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
 
-use base64::prelude::*;
+use base64::{engine::GeneralPurpose, prelude::*, read::DecoderReader, write::EncoderWriter};
 use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use thiserror::Error;
 
-pub fn base64_decode(bytes: &[u8]) -> Vec<u8> {
-    BASE64_URL_SAFE.decode(bytes).unwrap()
+const ENGINE: GeneralPurpose = BASE64_URL_SAFE;
+const COMPRESSION_LEVEL: u32 = 9;
+
+#[derive(Debug, Error)]
+pub enum EncodingError {
+    #[error("Invalid base64 data")]
+    Base64(#[from] base64::DecodeError),
+
+    #[error("Invalid zlib data")]
+    Zlib(#[source] io::Error),
+
+    #[error("Couldn't write encoded data")]
+    Write(#[source] io::Error),
+}
+
+pub type Result<T, E = EncodingError> = std::result::Result<T, E>;
+
+/// Reads base64 + zlib encoded data from `R` and yields the decoded bytes.
+pub type Decoder<R> = ZlibDecoder<DecoderReader<'static, GeneralPurpose, R>>;
+
+/// Creates a streaming decoder. Invalid input surfaces as an [`io::Error`] while reading.
+pub fn decoder<R: Read>(reader: R) -> Decoder<R> {
+    ZlibDecoder::new(DecoderReader::new(reader, &ENGINE))
+}
+
+/// Compresses and base64 encodes everything written to it into `W`.
+/// [`Encoder::finish`] has to be called to write the remaining data.
+pub struct Encoder<W: Write> {
+    inner: ZlibEncoder<EncoderWriter<'static, GeneralPurpose, W>>,
+}
+
+/// Creates a streaming encoder.
+pub fn encoder<W: Write>(writer: W) -> Encoder<W> {
+    Encoder {
+        inner: ZlibEncoder::new(EncoderWriter::new(writer, &ENGINE), Compression::new(COMPRESSION_LEVEL)),
+    }
+}
+
+impl<W: Write> Encoder<W> {
+    /// Flushes all remaining data and returns the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.inner.finish()?.finish()
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub fn base64_decode(bytes: &[u8]) -> Result<Vec<u8>> {
+    Ok(ENGINE.decode(bytes)?)
 }
 
 pub fn base64_encode(bytes: &[u8]) -> String {
-    BASE64_URL_SAFE.encode(bytes)
+    ENGINE.encode(bytes)
 }
 
-pub fn zlib_decode(bytes: &[u8]) -> Vec<u8> {
+pub fn zlib_decode(bytes: &[u8]) -> Result<Vec<u8>> {
     let mut output = Vec::with_capacity(bytes.len() * 2);
     let mut decoder = ZlibDecoder::new(bytes);
-    decoder.read_to_end(&mut output).unwrap();
-    output
+    decoder.read_to_end(&mut output).map_err(EncodingError::Zlib)?;
+    Ok(output)
 }
 
-pub fn zlib_encode(bytes: &[u8]) -> Vec<u8> {
-    let mut output = Vec::with_capacity(bytes.len());
-    let mut encoder = ZlibEncoder::new(&mut output, Compression::new(9));
-    encoder.write_all(bytes).unwrap();
-    drop(encoder); // why? // bro, try removing it and https://tryitands.ee
-    output
+pub fn zlib_encode(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::with_capacity(bytes.len()), Compression::new(COMPRESSION_LEVEL));
+    encoder.write_all(bytes).map_err(EncodingError::Write)?;
+    encoder.finish().map_err(EncodingError::Write)
 }
 
-pub fn decode(bytes: &[u8]) -> Vec<u8> {
-    let bytes = base64_decode(bytes);
+pub fn decode(bytes: &[u8]) -> Result<Vec<u8>> {
+    let bytes = base64_decode(bytes)?;
     zlib_decode(&bytes)
 }
 
-pub fn encode(bytes: &[u8]) -> String {
-    let bytes = zlib_encode(bytes);
-    base64_encode(&bytes)
+pub fn encode(bytes: &[u8]) -> Result<String> {
+    let bytes = zlib_encode(bytes)?;
+    Ok(base64_encode(&bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DATA: &[u8] = "1,Library,1,0,0,0;4451,Fire In The Hole,0,4442,29496,187|Sharks,https://www.sharkstunes.com".as_bytes();
+
+    #[test]
+    fn test_round_trip() {
+        let encoded = encode(DATA).unwrap();
+        assert_eq!(decode(encoded.as_bytes()).unwrap(), DATA);
+
+        let empty = encode(&[]).unwrap();
+        assert_eq!(decode(empty.as_bytes()).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_streaming_round_trip() {
+        let mut encoder = encoder(Vec::new());
+        for chunk in DATA.chunks(7) {
+            encoder.write_all(chunk).unwrap();
+        }
+        let encoded = encoder.finish().unwrap();
+
+        // streaming and in-memory encoding produce the same output
+        assert_eq!(encoded, encode(DATA).unwrap().as_bytes());
+
+        let mut decoded = Vec::new();
+        decoder(encoded.as_slice()).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, DATA);
+    }
+
+    #[test]
+    fn test_invalid_base64() {
+        assert!(matches!(decode(b"not base64!"), Err(EncodingError::Base64(_))));
+    }
+
+    #[test]
+    fn test_invalid_zlib() {
+        let not_zlib = base64_encode(DATA);
+        assert!(matches!(decode(not_zlib.as_bytes()), Err(EncodingError::Zlib(_))));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let compressed = zlib_encode(DATA).unwrap();
+        let truncated = base64_encode(&compressed[..compressed.len() / 2]);
+        assert!(matches!(decode(truncated.as_bytes()), Err(EncodingError::Zlib(_))));
+
+        let mut decoded = Vec::new();
+        assert!(decoder(truncated.as_bytes()).read_to_end(&mut decoded).is_err());
+    }
 }
@@ -0,0 +1,222 @@
+use ahash::HashMap;
+use itertools::Itertools;
+
+use crate::*;
+use crate::music::{Credit, Song, Tag};
+use crate::sfx::SfxLibraryEntry;
+
+/// A property of the entry with ID `id` which differs between two library versions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<T> {
+    pub id: EntryId,
+    pub old: T,
+    pub new: T,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SfxLibraryDiff {
+    pub old_version: String,
+    pub new_version: String,
+
+    pub added: Vec<SfxLibraryEntry>,
+    pub removed: Vec<SfxLibraryEntry>,
+    pub renamed: Vec<Change<String>>,
+    /// Entries whose parent category changed.
+    pub moved: Vec<Change<EntryId>>,
+    pub resized: Vec<Change<BytesSize>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MusicLibraryDiff {
+    pub old_version: EntryId,
+    pub new_version: EntryId,
+
+    pub added: Vec<Song>,
+    pub removed: Vec<Song>,
+    pub renamed: Vec<Change<String>>,
+    pub resized: Vec<Change<BytesSize>>,
+
+    pub new_tags: Vec<Tag>,
+    pub new_artists: Vec<Credit>,
+}
+
+impl SfxLibraryDiff {
+    pub fn between(old: &SfxLibrary, new: &SfxLibrary) -> Self {
+        let (added, removed, common) = compare_maps(&old.entries, &new.entries);
+
+        Self {
+            old_version: old.get_version().to_string(),
+            new_version: new.get_version().to_string(),
+
+            added,
+            removed,
+            // the root's name is the library version
+            renamed: changes(&common, |entry| (entry.id != new.root_id).then(|| entry.name.clone())),
+            moved: changes(&common, |entry| Some(entry.parent_id)),
+            resized: changes(&common, SfxLibraryEntry::bytes),
+        }
+    }
+
+    /// Whether the entries are the same, regardless of the version.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.moved.is_empty()
+            && self.resized.is_empty()
+    }
+}
+
+impl MusicLibraryDiff {
+    pub fn between(old: &MusicLibrary, new: &MusicLibrary) -> Self {
+        let (added, removed, common) = compare_maps(&old.songs, &new.songs);
+        let (new_tags, _, _) = compare_maps(&old.tags, &new.tags);
+        let (new_artists, _, _) = compare_maps(&old.credits, &new.credits);
+
+        Self {
+            old_version: old.version,
+            new_version: new.version,
+
+            added,
+            removed,
+            renamed: changes(&common, |song| Some(song.name.clone())),
+            resized: changes(&common, |song| Some(song.bytes)),
+
+            new_tags,
+            new_artists,
+        }
+    }
+
+    /// Whether the songs, tags and artists are the same, regardless of the version.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.resized.is_empty()
+            && self.new_tags.is_empty()
+            && self.new_artists.is_empty()
+    }
+}
+
+type Comparison<'a, V> = (Vec<V>, Vec<V>, Vec<(&'a V, &'a V)>);
+
+/// Splits two maps into added values, removed values and pairs of values present in both, each sorted by key.
+fn compare_maps<'a, K, V>(old: &'a HashMap<K, V>, new: &'a HashMap<K, V>) -> Comparison<'a, V>
+where
+    K: Ord + Eq + std::hash::Hash,
+    V: Clone,
+{
+    let added = new.iter()
+        .filter(|(key, _)| !old.contains_key(key))
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, value)| value.clone())
+        .collect();
+
+    let removed = old.iter()
+        .filter(|(key, _)| !new.contains_key(key))
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, value)| value.clone())
+        .collect();
+
+    let common = old.iter()
+        .filter_map(|(key, old_value)| new.get(key).map(|new_value| (key, old_value, new_value)))
+        .sorted_by(|(a, _, _), (b, _, _)| a.cmp(b))
+        .map(|(_, old_value, new_value)| (old_value, new_value))
+        .collect();
+
+    (added, removed, common)
+}
+
+fn changes<V: HasId, T: PartialEq>(common: &[(&V, &V)], property: impl Fn(&V) -> Option<T>) -> Vec<Change<T>> {
+    common.iter()
+        .filter_map(|(old, new)| match (property(old), property(new)) {
+            (Some(old_value), Some(new_value)) if old_value != new_value =>
+                Some(Change { id: new.id(), old: old_value, new: new_value }),
+            _ => None,
+        })
+        .collect()
+}
+
+trait HasId {
+    fn id(&self) -> EntryId;
+}
+
+impl HasId for SfxLibraryEntry {
+    fn id(&self) -> EntryId { self.id }
+}
+
+impl HasId for Song {
+    fn id(&self) -> EntryId { self.id }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn sfx_library(entries: &[&str]) -> SfxLibrary {
+        parse::sfx_library_from_str(&entries.join(";"))
+    }
+
+    fn ids(songs: &[Song]) -> Vec<EntryId> {
+        songs.iter().map(|song| song.id).collect()
+    }
+
+    #[test]
+    fn test_sfx_library_diff() {
+        let old = sfx_library(&[
+            "1,v1,1,0,0,0",
+            "2,Explosions,1,1,0,0",
+            "3,Hits,1,1,0,0",
+            "10,Boom,0,2,100,10",
+            "11,Bang,0,2,100,10",
+            "12,Thud,0,2,100,10",
+        ]);
+        let new = sfx_library(&[
+            "1,v2,1,0,0,0",
+            "2,Explosions,1,1,0,0",
+            "3,Hits,1,1,0,0",
+            "10,Big Boom,0,2,100,10",
+            "11,Bang,0,3,200,10",
+            "13,Crash,0,3,100,10",
+        ]);
+
+        let diff = SfxLibraryDiff::between(&old, &new);
+
+        assert_eq!(diff.old_version, "v1");
+        assert_eq!(diff.new_version, "v2");
+        assert_eq!(diff.added, vec![SfxLibraryEntry::from_str("13,Crash,0,3,100,10").unwrap()]);
+        assert_eq!(diff.removed, vec![SfxLibraryEntry::from_str("12,Thud,0,2,100,10").unwrap()]);
+        assert_eq!(diff.renamed, vec![Change { id: 10, old: "Boom".to_string(), new: "Big Boom".to_string() }]);
+        assert_eq!(diff.moved, vec![Change { id: 11, old: 2, new: 3 }]);
+        assert_eq!(diff.resized, vec![Change { id: 11, old: 100, new: 200 }]);
+
+        assert!(SfxLibraryDiff::between(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_music_library_diff() {
+        let old = parse::music_library_from_str("1\
+            |1,Waterflame,,\
+            |10000001,Glorious Morning,1,1000,60,.1.,0,,,0,,;10000002,Song,1,1000,60,.1.,0,,,0,,;10000003,Removed,1,1000,60,.1.,0,,,0,,\
+            |1,Action");
+        let new = parse::music_library_from_str("2\
+            |1,Waterflame,,;2,F-777,,\
+            |10000001,Glorious Morning,1,1000,60,.1.2.,0,,,0,,;10000002,Renamed Song,1,2000,60,.1.,0,,,0,,;10000004,Added,2,1000,60,.2.,0,,,1,,\
+            |1,Action;2,Calm");
+
+        let diff = MusicLibraryDiff::between(&old, &new);
+
+        assert_eq!(diff.old_version, 1);
+        assert_eq!(diff.new_version, 2);
+        assert_eq!(ids(&diff.added), vec![10000004]);
+        assert_eq!(ids(&diff.removed), vec![10000003]);
+        assert_eq!(diff.renamed, vec![Change { id: 10000002, old: "Song".to_string(), new: "Renamed Song".to_string() }]);
+        assert_eq!(diff.resized, vec![Change { id: 10000002, old: 1000, new: 2000 }]);
+        assert_eq!(diff.new_tags, vec![Tag::from_str("2,Calm").unwrap()]);
+        assert_eq!(diff.new_artists, vec![Credit::from_str("2,F-777,,").unwrap()]);
+
+        assert!(MusicLibraryDiff::between(&new, &new).is_empty());
+    }
+}
@@ -1,15 +1,23 @@
 use std::{path::{PathBuf, Path}, fs};
 
-use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
-use crate::{music::Song, requests, sfx::SfxLibraryEntry, EntryId};
+use crate::{error::Result, music::Song, sfx::SfxLibraryEntry, BytesSize, ContentSource, EntryId, IntegrityError, LibraryError, MusicLibrary, SfxLibrary};
+
+/// Every Ogg page starts with this.
+const OGG_CAPTURE_PATTERN: &[u8] = b"OggS";
+/// Length of an Ogg page header without its segment table.
+const OGG_HEADER_LENGTH: usize = 27;
+/// Header type flag of the first page of a stream.
+const OGG_BEGINNING_OF_STREAM: u8 = 0x02;
 
 #[derive(Copy, Clone)]
-pub struct SfxFileEntry(EntryId);
+pub struct SfxFileEntry(EntryId, Option<BytesSize>);
 #[derive(Copy, Clone)]
-pub struct MusicFileEntry(EntryId);
+pub struct MusicFileEntry(EntryId, Option<BytesSize>);
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FileEntryKind {
     Sound,
     Song,
@@ -20,6 +28,8 @@ pub trait FileEntry: Copy + Send {
     fn id(&self) -> EntryId;
     fn get_file_name(&self) -> String;
     fn kind(&self) -> FileEntryKind;
+    /// Size of the file according to the library, if it's known.
+    fn expected_size(&self) -> Option<BytesSize>;
     fn get_path(&self, gd_folder: impl AsRef<Path>) -> PathBuf {
         gd_folder.as_ref().join(self.get_file_name())
     }
@@ -29,25 +39,65 @@ pub trait FileEntry: Copy + Send {
     fn try_read_bytes(&self, gd_folder: impl AsRef<Path>) -> Option<Vec<u8>> {
         files::read_file(self.get_path(gd_folder)).ok()
     }
-    fn try_download_bytes(&self) -> Option<Vec<u8>> {
-        match self.kind() {
-            FileEntryKind::Sound => requests::request_sfx_file(&self.get_file_name()),
-            FileEntryKind::Song => requests::request_music_file(&self.get_file_name()),
-        }.ok()
-            .and_then(|response| response.bytes().ok())
-            .map(|bytes| bytes.to_vec())
-    }
+    /// Checks that `bytes` start with an Ogg stream and have the size given by the library.
+    fn verify_bytes(&self, bytes: &[u8]) -> Result<(), IntegrityError> {
+        let is_ogg = bytes.len() >= OGG_HEADER_LENGTH
+            && bytes.starts_with(OGG_CAPTURE_PATTERN)
+            && bytes[4] == 0 // stream structure version
+            && bytes[5] & OGG_BEGINNING_OF_STREAM != 0;
+        if !is_ogg {
+            return Err(IntegrityError::NotOgg)
+        }
+
+        match self.expected_size() {
+            Some(expected) if expected != bytes.len() as BytesSize =>
+                Err(IntegrityError::SizeMismatch { expected, found: bytes.len() as BytesSize }),
+            _ => Ok(()),
+        }
+    }
+    /// Checks the file in `gd_folder`, see [`FileEntry::verify_bytes`].
+    fn verify_file(&self, gd_folder: impl AsRef<Path>) -> Result<()> {
+        let path = self.get_path(gd_folder);
+        let bytes = fs::read(&path).map_err(LibraryError::io(path))?;
+        self.verify_bytes(&bytes).map_err(|source| corrupt(self, source))
+    }
+    /// Downloads the file, failing if the downloaded bytes don't pass [`FileEntry::verify_bytes`].
+    fn try_download_bytes(&self, source: &dyn ContentSource) -> Result<Vec<u8>> {
+        let bytes = match self.kind() {
+            FileEntryKind::Sound => source.fetch_sfx_file(&self.get_file_name()),
+            FileEntryKind::Song => source.fetch_music_file(&self.get_file_name()),
+        }?;
+        self.verify_bytes(&bytes).map_err(|source| corrupt(self, source))?;
+        Ok(bytes)
+    }
+    /// Verifies `bytes` and writes them to a temporary file which then replaces the file,
+    /// so an interrupted write never leaves a partial file behind.
     fn try_write_bytes(&self, gd_folder: impl AsRef<Path>, bytes: Vec<u8>) -> Result<()> {
-        files::write_file(self.get_path(gd_folder), bytes)
+        self.verify_bytes(&bytes).map_err(|source| corrupt(self, source))?;
+
+        let path = self.get_path(gd_folder);
+        let temp_path = path.with_extension("ogg.tmp");
+
+        fs::write(&temp_path, bytes)
+            .and_then(|()| fs::rename(&temp_path, &path))
+            .map_err(|err| {
+                let _ = fs::remove_file(&temp_path);
+                LibraryError::io(path)(err)
+            })
     }
     fn try_delete_file(&self, gd_folder: impl AsRef<Path>) -> Result<()> {
-        Ok(fs::remove_file(self.get_path(gd_folder))?)
+        let path = self.get_path(gd_folder);
+        fs::remove_file(&path).map_err(LibraryError::io(path))
     }
 }
 
+fn corrupt(file_entry: &impl FileEntry, source: IntegrityError) -> LibraryError {
+    LibraryError::Corrupt { file: file_entry.get_file_name(), source }
+}
+
 impl FileEntry for SfxFileEntry {
     fn new(id: EntryId) -> Self {
-        Self(id)
+        Self(id, None)
     }
     fn id(&self) -> EntryId {
         self.0
@@ -58,11 +108,14 @@ impl FileEntry for SfxFileEntry {
     fn kind(&self) -> FileEntryKind {
         FileEntryKind::Sound
     }
+    fn expected_size(&self) -> Option<BytesSize> {
+        self.1
+    }
 }
 
 impl FileEntry for MusicFileEntry {
     fn new(id: EntryId) -> Self {
-        Self(id)
+        Self(id, None)
     }
     fn id(&self) -> EntryId {
         self.0
@@ -73,16 +126,91 @@ impl FileEntry for MusicFileEntry {
     fn kind(&self) -> FileEntryKind {
         FileEntryKind::Song
     }
+    fn expected_size(&self) -> Option<BytesSize> {
+        self.1
+    }
+}
+
+impl SfxFileEntry {
+    pub fn with_expected_size(id: EntryId, expected_size: Option<BytesSize>) -> Self {
+        Self(id, expected_size)
+    }
+}
+
+impl MusicFileEntry {
+    pub fn with_expected_size(id: EntryId, expected_size: Option<BytesSize>) -> Self {
+        Self(id, expected_size)
+    }
+}
+
+/// Sound files are named `s<id>.ogg`, song files `<id>.ogg`.
+pub fn parse_file_name(name: &str) -> Option<(FileEntryKind, EntryId)> {
+    let name = name.strip_suffix(".ogg")?;
+    match name.strip_prefix('s') {
+        Some(id) => Some((FileEntryKind::Sound, id.parse().ok()?)),
+        None => Some((FileEntryKind::Song, name.parse().ok()?)),
+    }
+}
+
+/// Sizes of 0 mean the size is unknown.
+fn known_size(bytes: BytesSize) -> Option<BytesSize> {
+    (bytes > 0).then_some(bytes)
 }
 
 impl SfxLibraryEntry {
     pub fn into_file_entry(&self) -> SfxFileEntry {
-        SfxFileEntry::new(self.id)
+        SfxFileEntry(self.id, self.bytes().and_then(known_size))
     }
 }
 
 impl Song {
     pub fn into_file_entry(&self) -> MusicFileEntry {
-        MusicFileEntry::new(self.id)
+        MusicFileEntry(self.id, known_size(self.bytes))
+    }
+}
+
+impl SfxLibrary {
+    /// File entry of the sound with `id`, with its expected size if it's in the library.
+    pub fn file_entry(&self, id: EntryId) -> SfxFileEntry {
+        self.entries().get(&id).map_or_else(|| SfxFileEntry::new(id), SfxLibraryEntry::into_file_entry)
+    }
+}
+
+impl MusicLibrary {
+    /// File entry of the song with `id`, with its expected size if it's in the library.
+    pub fn file_entry(&self, id: EntryId) -> MusicFileEntry {
+        self.songs.get(&id).map_or_else(|| MusicFileEntry::new(id), Song::into_file_entry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ogg_bytes(len: usize) -> Vec<u8> {
+        let mut bytes = b"OggS\0\x02".to_vec();
+        bytes.resize(len, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_verify_bytes() {
+        let sound = SfxFileEntry(4451, Some(100));
+
+        assert_eq!(sound.verify_bytes(&ogg_bytes(100)), Ok(()));
+        assert_eq!(sound.verify_bytes(&ogg_bytes(60)), Err(IntegrityError::SizeMismatch { expected: 100, found: 60 }));
+        assert_eq!(sound.verify_bytes(b"<!DOCTYPE html><html>Not Found</html>"), Err(IntegrityError::NotOgg));
+        assert_eq!(sound.verify_bytes(b"OggS"), Err(IntegrityError::NotOgg));
+        // without a known size any complete header is fine
+        assert_eq!(SfxFileEntry::new(4451).verify_bytes(&ogg_bytes(60)), Ok(()));
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(parse_file_name("s4451.ogg"), Some((FileEntryKind::Sound, 4451)));
+        assert_eq!(parse_file_name("10000001.ogg"), Some((FileEntryKind::Song, 10000001)));
+        assert_eq!(parse_file_name("s4451.ogg.tmp"), None);
+        assert_eq!(parse_file_name("CCGameManager.dat"), None);
+        assert_eq!(parse_file_name("sfx.ogg"), None);
     }
 }
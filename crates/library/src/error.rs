@@ -0,0 +1,168 @@
+use std::{error::Error, io, path::{Path, PathBuf}};
+
+use thiserror::Error;
+
+use crate::{BytesSize, EntryId};
+
+pub type Result<T, E = LibraryError> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum LibraryError {
+    /// The request couldn't be sent, or the response body couldn't be read.
+    #[error("Network request to {url} failed")]
+    Network {
+        url: String,
+        #[source] source: reqwest::Error,
+    },
+
+    #[error("{url} responded with HTTP status {status}")]
+    HttpStatus {
+        url: String,
+        status: u16,
+    },
+
+    #[error("Couldn't access {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source] source: io::Error,
+    },
+
+    /// The library data isn't valid base64, zlib or UTF-8.
+    #[error("Couldn't decode library data")]
+    Decode(#[source] Box<dyn Error + Send + Sync>),
+
+    #[error("Couldn't encode library data")]
+    Encode(#[from] files::encoding::EncodingError),
+
+    /// The library data doesn't have the expected overall structure.
+    #[error("Malformed library data: {0}")]
+    Malformed(String),
+
+    /// A single record couldn't be parsed. `record` is the index of the record within its section.
+    #[error("Invalid record #{record} in {section}")]
+    Parse {
+        section: &'static str,
+        record: usize,
+        #[source] source: ParseError,
+    },
+
+    #[error("Couldn't export library to {}", path.display())]
+    Export {
+        path: PathBuf,
+        #[source] source: Box<dyn Error + Send + Sync>,
+    },
+
+    /// Downloaded bytes which would have been written as a corrupt sound or song file.
+    #[error("Downloaded {file} is corrupt")]
+    Corrupt {
+        file: String,
+        #[source] source: IntegrityError,
+    },
+
+    #[error("Offline mode is enabled, {path} can't be downloaded")]
+    Offline {
+        path: String,
+    },
+
+    #[error("Offline mode is enabled, but the local library {} is missing or invalid", path.display())]
+    OfflineLibraryUnavailable {
+        path: PathBuf,
+        #[source] source: Box<LibraryError>,
+    },
+}
+
+/// Why the bytes of a sound or song file can't be right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum IntegrityError {
+    /// Usually an interrupted download.
+    #[error("Expected {expected} bytes, found {found}")]
+    SizeMismatch {
+        expected: BytesSize,
+        found: BytesSize,
+    },
+
+    /// E.g. an HTML error page instead of the file.
+    #[error("Not an Ogg file")]
+    NotOgg,
+}
+
+/// A recoverable problem found while parsing a library. The affected data is kept as well as possible.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LibraryWarning {
+    #[error("Library data isn't valid UTF-8 after byte {valid_up_to}, invalid characters were replaced")]
+    InvalidUtf8 {
+        valid_up_to: usize,
+    },
+
+    #[error("Couldn't URL-decode the {field} of {section} #{id}, kept {value:?} as is")]
+    InvalidUrlEncoding {
+        section: &'static str,
+        id: EntryId,
+        field: &'static str,
+        value: String,
+    },
+
+    /// A record couldn't be parsed and isn't part of the library. `record` is its index within the section.
+    #[error("Left out invalid record #{record} in {section}: {raw:?}")]
+    DroppedRecord {
+        section: &'static str,
+        record: usize,
+        raw: String,
+        #[source] source: ParseError,
+    },
+
+    /// Another record with the same ID came first, so this one was left out.
+    #[error("Left out record with duplicate ID {id} in {section}")]
+    DuplicateId {
+        section: &'static str,
+        id: EntryId,
+    },
+}
+
+impl LibraryWarning {
+    pub fn is_dropped_record(&self) -> bool {
+        matches!(self, Self::DroppedRecord { .. } | Self::DuplicateId { .. })
+    }
+}
+
+/// Why a single comma-separated record couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ParseError {
+    #[error("Expected at least {expected} fields, found {found}")]
+    FieldCount {
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("Invalid {field} {value:?}")]
+    InvalidField {
+        field: &'static str,
+        value: String,
+    },
+}
+
+impl LibraryError {
+    pub(crate) fn io(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| Self::Io { path, source }
+    }
+
+    pub(crate) fn decode(source: impl Error + Send + Sync + 'static) -> Self {
+        Self::Decode(Box::new(source))
+    }
+
+    /// Whether trying again later might succeed, e.g. after a dropped connection or a server error.
+    /// Missing files and local problems won't go away by themselves.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Network { .. } => true,
+            Self::HttpStatus { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// Whether the server doesn't have the requested file.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::HttpStatus { status: 404, .. })
+    }
+}
@@ -0,0 +1,492 @@
+use std::{error::Error, fs::File, io::{BufWriter, Write}, path::Path};
+use std::time::Duration;
+
+use ahash::HashSet;
+use itertools::Itertools;
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection};
+use serde::{Serialize, Serializer};
+
+use crate::*;
+use crate::music::{Credit, Song, Tag};
+use crate::sfx::{EntryKind, SfxLibraryEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl ExportFormat {
+    #[cfg(feature = "sqlite")]
+    pub const ALL: &'static [ExportFormat] = &[Self::Json, Self::Csv, Self::Sqlite];
+    #[cfg(not(feature = "sqlite"))]
+    pub const ALL: &'static [ExportFormat] = &[Self::Json, Self::Csv];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Csv => "CSV",
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => "SQLite",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => "sqlite",
+        }
+    }
+}
+
+/// Durations are exported as (fractional) seconds.
+pub(crate) fn serialize_duration<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+impl SfxLibrary {
+    /// Writes the library to `path`, replacing the file if it exists.
+    pub fn export(&self, format: ExportFormat, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        match format {
+            ExportFormat::Json => export_file(path, |writer| write_sfx_json(self, writer)),
+            ExportFormat::Csv => export_file(path, |writer| write_sfx_csv(self, writer)),
+            #[cfg(feature = "sqlite")]
+            ExportFormat::Sqlite => export_database(path, |connection| write_sfx_sqlite(self, connection)),
+        }
+    }
+}
+
+impl MusicLibrary {
+    /// Writes the library to `path`, replacing the file if it exists.
+    pub fn export(&self, format: ExportFormat, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        match format {
+            ExportFormat::Json => export_file(path, |writer| write_music_json(self, writer)),
+            ExportFormat::Csv => export_file(path, |writer| write_music_csv(self, writer)),
+            #[cfg(feature = "sqlite")]
+            ExportFormat::Sqlite => export_database(path, |connection| write_music_sqlite(self, connection)),
+        }
+    }
+}
+
+fn export_error<E: Error + Send + Sync + 'static>(path: &Path) -> impl FnOnce(E) -> LibraryError + '_ {
+    move |source| LibraryError::Export { path: path.to_path_buf(), source: Box::new(source) }
+}
+
+fn export_file<E: Error + Send + Sync + 'static>(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), E>,
+) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path).map_err(LibraryError::io(path))?);
+    write(&mut writer).map_err(export_error(path))?;
+    writer.flush().map_err(LibraryError::io(path))
+}
+
+#[cfg(feature = "sqlite")]
+fn export_database(path: &Path, write: impl FnOnce(&mut Connection) -> rusqlite::Result<()>) -> Result<()> {
+    // start from an empty database instead of adding tables to an existing one
+    if path.exists() {
+        std::fs::remove_file(path).map_err(LibraryError::io(path))?;
+    }
+    let mut connection = Connection::open(path).map_err(export_error(path))?;
+    write(&mut connection).map_err(export_error(path))
+}
+
+#[derive(Serialize)]
+struct SfxJson<'a> {
+    version: &'a str,
+    root: SfxJsonNode<'a>,
+    /// Entries which can't be reached from the root, e.g. because their parent is missing, with their children.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    orphans: Vec<SfxJsonNode<'a>>,
+    credits: &'a [sfx::Credit],
+}
+
+#[derive(Serialize)]
+struct SfxJsonNode<'a> {
+    #[serde(flatten)]
+    entry: &'a SfxLibraryEntry,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<SfxJsonNode<'a>>,
+}
+
+/// Writes the library as a tree of categories and sounds, starting at the root category.
+/// Like CSV and SQLite, it contains every entry: those which aren't part of the tree are listed as `orphans`.
+pub fn write_sfx_json(library: &SfxLibrary, writer: impl Write) -> serde_json::Result<()> {
+    // `visited` guards against entries being listed twice in malformed libraries
+    fn node<'a>(library: &'a SfxLibrary, entry: &'a SfxLibraryEntry, visited: &mut HashSet<EntryId>) -> SfxJsonNode<'a> {
+        visited.insert(entry.id);
+        let children = library.iter_children(entry)
+            .filter(|child| !visited.contains(&child.id))
+            .collect_vec()
+            .into_iter()
+            .map(|child| node(library, child, visited))
+            .collect();
+        SfxJsonNode { entry, children }
+    }
+
+    let mut visited = HashSet::default();
+    let root = node(library, library.get_root(), &mut visited);
+
+    // in library order, so that orphaned categories usually come before their children
+    let mut orphans = Vec::new();
+    for id in &library.entry_order {
+        let Some(entry) = library.get_entry(*id) else { continue };
+        if !visited.contains(id) {
+            orphans.push(node(library, entry, &mut visited));
+        }
+    }
+
+    let json = SfxJson {
+        version: library.get_version(),
+        root,
+        orphans,
+        credits: &library.credits,
+    };
+    serde_json::to_writer_pretty(writer, &json)
+}
+
+#[derive(Serialize)]
+struct MusicJson<'a> {
+    version: EntryId,
+    songs: Vec<&'a Song>,
+    artists: Vec<&'a Credit>,
+    tags: Vec<&'a Tag>,
+}
+
+/// Writes the songs, artists and tags of the library, each sorted by ID.
+pub fn write_music_json(library: &MusicLibrary, writer: impl Write) -> serde_json::Result<()> {
+    let json = MusicJson {
+        version: library.version,
+        songs: library.songs.values().sorted_by_key(|song| song.id).collect(),
+        artists: library.credits.values().sorted_by_key(|credit| credit.id).collect(),
+        tags: library.tags.values().sorted_by_key(|tag| tag.id).collect(),
+    };
+    serde_json::to_writer_pretty(writer, &json)
+}
+
+/// Path of the category containing `entry`.
+fn category_path(library: &SfxLibrary, entry: &SfxLibraryEntry) -> String {
+    library.path_string(entry.parent_id)
+}
+
+fn sorted_sfx_entries(library: &SfxLibrary) -> impl Iterator<Item = &SfxLibraryEntry> {
+    library.entries.values()
+        .filter(|entry| entry.id != library.root_id)
+        .sorted_by_key(|entry| entry.id)
+}
+
+fn duration_secs(entry: &SfxLibraryEntry) -> Option<f64> {
+    entry.duration().map(|duration| duration.as_secs_f64())
+}
+
+fn kind_name(kind: &EntryKind) -> &'static str {
+    match kind {
+        EntryKind::Category => "category",
+        EntryKind::Sound { .. } => "sound",
+    }
+}
+
+/// Writes one row per category and sound, excluding the root. Categories have empty `bytes` and `duration`.
+pub fn write_sfx_csv(library: &SfxLibrary, writer: impl Write) -> csv::Result<()> {
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record(["id", "name", "kind", "parent_id", "path", "bytes", "duration"])?;
+
+    for entry in sorted_sfx_entries(library) {
+        csv.write_record([
+            entry.id.to_string(),
+            entry.name.clone(),
+            kind_name(&entry.kind).to_string(),
+            entry.parent_id.to_string(),
+            category_path(library, entry),
+            entry.bytes().map(|bytes| bytes.to_string()).unwrap_or_default(),
+            duration_secs(entry).map(|duration| duration.to_string()).unwrap_or_default(),
+        ])?;
+    }
+
+    csv.flush()?;
+    Ok(())
+}
+
+fn tag_names(library: &MusicLibrary, song: &Song) -> String {
+    song.tags.iter()
+        .map(|id| library.tags.get(id).map(|tag| tag.name.clone()).unwrap_or_else(|| id.to_string()))
+        .join(";")
+}
+
+/// Writes one row per song, with the artist name and tag names resolved.
+pub fn write_music_csv(library: &MusicLibrary, writer: impl Write) -> csv::Result<()> {
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record([
+        "id", "name", "artist_id", "artist", "extra_artist_ids", "bytes", "duration", "tags", "ncs", "new", "priority", "new_type", "external_link",
+    ])?;
+
+    for song in library.songs.values().sorted_by_key(|song| song.id) {
+        let artist = library.credits.get(&song.credit_id).map(|credit| credit.name.as_str()).unwrap_or_default();
+
+        csv.write_record([
+            song.id.to_string(),
+            song.name.clone(),
+            song.credit_id.to_string(),
+            artist.to_string(),
+            song.extra_artist_ids.iter().join(";"),
+            song.bytes.to_string(),
+            song.duration.as_secs_f64().to_string(),
+            tag_names(library, song),
+            song.ncs.to_string(),
+            song.new.to_string(),
+            song.priority.map(|priority| priority.to_string()).unwrap_or_default(),
+            song.new_type.map(|new_type| new_type.to_string()).unwrap_or_default(),
+            song.external_link.clone().unwrap_or_default(),
+        ])?;
+    }
+
+    csv.flush()?;
+    Ok(())
+}
+
+/// Creates the `sfx_entries` and `sfx_credits` tables and fills them.
+#[cfg(feature = "sqlite")]
+pub fn write_sfx_sqlite(library: &SfxLibrary, connection: &mut Connection) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+
+    transaction.execute_batch("
+        CREATE TABLE sfx_entries (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            parent_id INTEGER NOT NULL,
+            path TEXT NOT NULL,
+            bytes INTEGER,
+            duration REAL
+        );
+        CREATE TABLE sfx_credits (
+            name TEXT NOT NULL,
+            link TEXT NOT NULL
+        );
+    ")?;
+
+    {
+        let mut insert_entry = transaction.prepare("INSERT INTO sfx_entries VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+        for entry in sorted_sfx_entries(library) {
+            insert_entry.execute(params![
+                entry.id,
+                entry.name,
+                kind_name(&entry.kind),
+                entry.parent_id,
+                category_path(library, entry),
+                // SQLite integers are signed
+                entry.bytes().map(|bytes| bytes as i64),
+                duration_secs(entry),
+            ])?;
+        }
+
+        let mut insert_credit = transaction.prepare("INSERT INTO sfx_credits VALUES (?1, ?2)")?;
+        for credit in &library.credits {
+            insert_credit.execute(params![credit.name, credit.link])?;
+        }
+    }
+
+    transaction.commit()
+}
+
+/// Creates the `songs`, `artists`, `tags`, `song_tags` and `song_extra_artists` tables and fills them.
+#[cfg(feature = "sqlite")]
+pub fn write_music_sqlite(library: &MusicLibrary, connection: &mut Connection) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+
+    transaction.execute_batch("
+        CREATE TABLE artists (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            url TEXT,
+            yt_url TEXT
+        );
+        CREATE TABLE tags (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL
+        );
+        CREATE TABLE songs (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            artist_id INTEGER NOT NULL,
+            bytes INTEGER NOT NULL,
+            duration REAL NOT NULL,
+            ncs INTEGER NOT NULL,
+            new INTEGER NOT NULL,
+            priority INTEGER,
+            new_type INTEGER,
+            external_link TEXT
+        );
+        CREATE TABLE song_tags (
+            song_id INTEGER NOT NULL,
+            tag_id INTEGER NOT NULL,
+            PRIMARY KEY (song_id, tag_id)
+        );
+        CREATE TABLE song_extra_artists (
+            song_id INTEGER NOT NULL,
+            artist_id INTEGER NOT NULL,
+            PRIMARY KEY (song_id, artist_id)
+        );
+    ")?;
+
+    {
+        let mut insert_artist = transaction.prepare("INSERT INTO artists VALUES (?1, ?2, ?3, ?4)")?;
+        for credit in library.credits.values().sorted_by_key(|credit| credit.id) {
+            insert_artist.execute(params![credit.id, credit.name, credit.url, credit.yt_url])?;
+        }
+
+        let mut insert_tag = transaction.prepare("INSERT INTO tags VALUES (?1, ?2)")?;
+        for tag in library.tags.values().sorted_by_key(|tag| tag.id) {
+            insert_tag.execute(params![tag.id, tag.name])?;
+        }
+
+        let mut insert_song = transaction.prepare("INSERT INTO songs VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)")?;
+        let mut insert_song_tag = transaction.prepare("INSERT OR IGNORE INTO song_tags VALUES (?1, ?2)")?;
+        let mut insert_extra_artist = transaction.prepare("INSERT OR IGNORE INTO song_extra_artists VALUES (?1, ?2)")?;
+        for song in library.songs.values().sorted_by_key(|song| song.id) {
+            insert_song.execute(params![
+                song.id,
+                song.name,
+                song.credit_id,
+                song.bytes as i64,
+                song.duration.as_secs_f64(),
+                song.ncs,
+                song.new,
+                song.priority,
+                song.new_type,
+                song.external_link,
+            ])?;
+            for tag_id in &song.tags {
+                insert_song_tag.execute(params![song.id, tag_id])?;
+            }
+            for artist_id in &song.extra_artist_ids {
+                insert_extra_artist.execute(params![song.id, artist_id])?;
+            }
+        }
+    }
+
+    transaction.commit()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sfx_library() -> SfxLibrary {
+        parse::sfx_library_from_str("1,v1,1,0,0,0;2,Explosions,1,1,0,0;3,Big,1,2,0,0;10,Boom,0,3,100,150;11,\"Bang\",0,2,200,10")
+    }
+
+    fn music_library() -> MusicLibrary {
+        parse::music_library_from_str("7\
+            |1,Waterflame,https%3A%2F%2Fwaterflame.com,;2,F-777,,\
+            |10000002,Ricochet Love,2,2000,90,.2.,0,,,1,3,;10000001,Glorious Morning,1,1000,60,.1.2.,1,2,https%3A%2F%2Fexample.com,0,,\
+            |1,Action;2,Calm")
+    }
+
+    #[test]
+    fn test_sfx_csv() {
+        let mut output = Vec::new();
+        write_sfx_csv(&sfx_library(), &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "\
+id,name,kind,parent_id,path,bytes,duration
+2,Explosions,category,1,,,
+3,Big,category,2,Explosions,,
+10,Boom,sound,3,Explosions/Big,100,1.5
+11,\"\"\"Bang\"\"\",sound,2,Explosions,200,0.1
+");
+    }
+
+    #[test]
+    fn test_sfx_json() {
+        let mut output = Vec::new();
+        write_sfx_json(&sfx_library(), &mut output).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(json["version"], "v1");
+        let explosions = &json["root"]["children"][0];
+        assert_eq!(explosions["name"], "Explosions");
+        assert_eq!(explosions["kind"], "category");
+        assert_eq!(explosions["children"][0]["children"][0]["duration"], 1.5);
+        assert!(json.get("orphans").is_none());
+
+        // entries whose parent is missing are still exported
+        let library = parse::sfx_library_from_str("1,v1,1,0,0,0;2,Explosions,1,1,0,0;5,Lost,1,4,0,0;12,Pop,0,5,50,20");
+        let mut output = Vec::new();
+        write_sfx_json(&library, &mut output).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(json["orphans"][0]["name"], "Lost");
+        assert_eq!(json["orphans"][0]["children"][0]["name"], "Pop");
+        assert_eq!(json["orphans"].as_array().unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sfx_sqlite() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        write_sfx_sqlite(&sfx_library(), &mut connection).unwrap();
+
+        let (path, bytes): (String, i64) = connection
+            .query_row("SELECT path, bytes FROM sfx_entries WHERE id = 10", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(path, "Explosions/Big");
+        assert_eq!(bytes, 100);
+    }
+
+    #[test]
+    fn test_music_csv() {
+        let mut output = Vec::new();
+        write_music_csv(&music_library(), &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "\
+id,name,artist_id,artist,extra_artist_ids,bytes,duration,tags,ncs,new,priority,new_type,external_link
+10000001,Glorious Morning,1,Waterflame,2,1000,60,Action;Calm,true,false,,,https://example.com
+10000002,Ricochet Love,2,F-777,,2000,90,Calm,false,true,3,,
+");
+    }
+
+    #[test]
+    fn test_music_json() {
+        let mut output = Vec::new();
+        write_music_json(&music_library(), &mut output).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(json["version"], 7);
+        assert_eq!(json["songs"][0]["name"], "Glorious Morning");
+        assert_eq!(json["songs"][0]["tags"], serde_json::json!([1, 2]));
+        assert_eq!(json["songs"][1]["duration"], 90.0);
+        assert!(json["songs"][0].get("extra_fields").is_none());
+        assert_eq!(json["artists"][0]["url"], "https://waterflame.com");
+        assert_eq!(json["tags"][1]["name"], "Calm");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_music_sqlite() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        write_music_sqlite(&music_library(), &mut connection).unwrap();
+
+        let artist: String = connection
+            .query_row("SELECT artists.name FROM songs JOIN artists ON artists.id = songs.artist_id WHERE songs.id = 10000002", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(artist, "F-777");
+
+        let tags: i64 = connection
+            .query_row("SELECT COUNT(*) FROM song_tags WHERE song_id = 10000001", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(tags, 2);
+
+        let extra_artist: i64 = connection
+            .query_row("SELECT artist_id FROM song_extra_artists WHERE song_id = 10000001", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(extra_artist, 2);
+    }
+}
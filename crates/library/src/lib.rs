@@ -1,23 +1,33 @@
 use std::time::Duration;
 
 use ahash::HashMap;
-use anyhow::Result;
 
-pub mod requests;
+pub mod error;
+pub mod source;
 pub mod parse;
 pub mod sfx;
 pub mod music;
 pub mod entries;
+pub mod diff;
+pub mod export;
+pub mod validate;
+pub mod tree;
+pub mod search;
 
 pub use entries::*;
+pub use error::{IntegrityError, LibraryError, LibraryWarning, ParseError};
+use error::Result;
+pub use source::{ContentSource, HttpSource, LocalSource, OfflineSource};
 
 pub type EntryId = u32;
 pub type BytesSize = u64;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct SfxLibrary {
     pub root_id: EntryId,
     pub sound_ids: Vec<EntryId>,
+    /// IDs of all entries in the order they appear in the library file, so that it's written back unchanged.
+    pub entry_order: Vec<EntryId>,
 
     pub entries: HashMap<EntryId, sfx::SfxLibraryEntry>,
     pub child_map: HashMap<EntryId, Vec<EntryId>>,
@@ -26,13 +36,19 @@ pub struct SfxLibrary {
     pub total_duration: Duration,
 
     pub credits: Vec<sfx::Credit>,
+
+    /// Problems which were worked around while parsing, including records which had to be left out.
+    pub warnings: Vec<LibraryWarning>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default, PartialEq)]
 pub struct MusicLibrary {
     pub version: EntryId,
 
     pub credits: HashMap<EntryId, music::Credit>,
     pub songs: HashMap<EntryId, music::Song>,
     pub tags: HashMap<music::TagId, music::Tag>,
+
+    /// Problems which were worked around while parsing, including records which had to be left out.
+    pub warnings: Vec<LibraryWarning>,
 }
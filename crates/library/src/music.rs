@@ -1,68 +1,109 @@
 // <version>|{credit};{credit}|{song};{song}|{tag};{tag}
 // version = number
-// credit = <id>,<name>,<url>,<youtube channel id>
-// songs = <id>,<name>,<credit id>,<bytes>,<duration>,.{tag id}.{tag id}.
-// tag = <id>,<name>
+// credit = <id>,<name>,<url>,<youtube channel id>{,<extra field>}
+// songs = <id>,<name>,<credit id>,<bytes>,<duration>,.{tag id}.{tag id}.,<ncs>,{extra credit id}.{extra credit id},<external link>,<new>,<priority>,<new type>{,<extra field>}
+// tag = <id>,<name>{,<extra field>}
 
 use std::fmt::Display;
 use std::time::Duration;
-use std::path::Path;
+use std::{fs, path::Path};
+
+use itertools::Itertools;
+use serde::Serialize;
 
 use crate::*;
 
 pub type TagId = u16;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Credit {
     pub id: EntryId,
     pub name: String,
     pub url: Option<String>,
     pub yt_url: Option<String>,
+    /// `url` as it was escaped in the library file, so that it's written back unchanged.
+    #[serde(skip)]
+    pub raw_url: Option<String>,
+    /// Fields after the known ones, kept so that they are written back unchanged.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_fields: Vec<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
 pub struct Song {
     pub id: EntryId,
     pub name: String,
     pub credit_id: EntryId,
     pub bytes: BytesSize,
+    #[serde(serialize_with = "export::serialize_duration")]
     pub duration: Duration,
     pub tags: Vec<TagId>,
     pub ncs: bool,
-    pub unk2: String,
-    pub url: String,
+    /// Featured artists in addition to the main artist `credit_id`.
+    pub extra_artist_ids: Vec<EntryId>,
+    /// Link to the song outside of GD, e.g. on the artist's website.
+    pub external_link: Option<String>,
+    /// `external_link` as it was escaped in the library file, so that it's written back unchanged.
+    #[serde(skip)]
+    pub raw_external_link: Option<String>,
     pub new: bool,
-    pub unk4: String,
-    pub unk5: String,
+    /// Used by GD to order songs, `None` if the field is empty.
+    pub priority: Option<u32>,
+    /// Distinguishes kinds of songs flagged as `new`, `None` if the field is empty.
+    pub new_type: Option<u32>,
+    /// Fields after the known ones, kept so that they are written back unchanged.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_fields: Vec<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Tag {
     pub id: TagId,
     pub name: String,
+    /// Fields after the known ones, kept so that they are written back unchanged.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_fields: Vec<String>,
 }
 
 impl MusicLibrary {
-    pub fn load(gd_folder: impl AsRef<Path>) -> Result<Self> {
+    pub fn load(gd_folder: impl AsRef<Path>, source: &dyn ContentSource) -> Result<Self> {
+        Self::load_with_previous(gd_folder, source).map(|(library, _)| library)
+    }
+
+    /// Like [`Self::load`], but also returns the local library if it was replaced by an update.
+    pub fn load_with_previous(gd_folder: impl AsRef<Path>, source: &dyn ContentSource) -> Result<(Self, Option<Self>)> {
         const MUSIC_LIBRARY_FILE: &str = "musiclibrary.dat";
         const MUSIC_LIBRARY_REQUEST: &str = "musiclibrary_02.dat";
 
         let file = gd_folder.as_ref().join(MUSIC_LIBRARY_FILE);
 
-        let local_library = files::read_file(&file)
+        let local_library = fs::read(&file)
+            .map_err(LibraryError::io(&file))
             .and_then(parse::parse_music_library_from_bytes);
 
-        if !Self::should_try_update(local_library.as_ref().ok()) {
+        if source.is_offline() {
             return local_library
+                .map(|library| (library, None))
+                .map_err(|source| LibraryError::OfflineLibraryUnavailable {
+                    path: file,
+                    source: Box::new(source),
+                })
         }
 
-        requests::request_music_file(MUSIC_LIBRARY_REQUEST)
-            .and_then(|response| {
-                let bytes = response.bytes()?.to_vec();
-                let _ = files::write_file(&file, &bytes);
-                parse::parse_music_library_from_bytes(bytes)
-            })
-            .or_else(|download_err| local_library.map_err(|_| download_err))
+        if !Self::should_try_update(local_library.as_ref().ok(), source) {
+            return local_library.map(|library| (library, None))
+        }
+
+        match source.fetch_music_file(MUSIC_LIBRARY_REQUEST).and_then(|bytes| {
+            let library = parse::parse_music_library_from_bytes(bytes.clone())?;
+            let _ = fs::write(&file, &bytes);
+            Ok(library)
+        }) {
+            Ok(library) => Ok((library, local_library.ok())),
+            Err(download_err) => local_library
+                .map(|library| (library, None))
+                .map_err(|_| download_err),
+        }
     }
 
     pub fn total_bytes(&self) -> BytesSize {
@@ -77,18 +118,36 @@ impl MusicLibrary {
             .sum()
     }
 
-    fn should_try_update(library: Option<&MusicLibrary>) -> bool {
+    /// Number of records which couldn't be parsed and were left out.
+    pub fn dropped_records(&self) -> usize {
+        self.warnings.iter().filter(|warning| warning.is_dropped_record()).count()
+    }
+
+    /// Serializes the library into the encoded `musiclibrary.dat` format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let string = parse::write_music_library_to_string(self);
+        Ok(files::encoding::encode(string.as_bytes())?.into_bytes())
+    }
+
+    fn should_try_update(library: Option<&MusicLibrary>, source: &dyn ContentSource) -> bool {
         const MUSIC_VERSION_ENDPOINT: &str = "musiclibrary_version_02.txt";
 
         let Some(library) = library else { return true };
 
-        requests::request_music_file(MUSIC_VERSION_ENDPOINT).ok()
-            .and_then(|response| response.text().ok())
+        source.fetch_music_file(MUSIC_VERSION_ENDPOINT).ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
             .map(|version| version != library.version.to_string())
             .unwrap_or(false) // request failed, don't bother updating
     }
 }
 
+impl Song {
+    /// The main artist followed by the featured artists.
+    pub fn artist_ids(&self) -> impl Iterator<Item = EntryId> + '_ {
+        std::iter::once(self.credit_id).chain(self.extra_artist_ids.iter().copied())
+    }
+}
+
 impl Display for Song {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&format!(
@@ -100,12 +159,14 @@ impl Display for Song {
             self.duration.as_secs(),
             self.tags.iter()
                 .fold(String::new(), |s, n| format!("{s}.{n}")),
-            self.ncs,
-            self.unk2,
-            self.url,
-            self.new,
-            self.unk4,
-            self.unk5,
-        ))
+            self.ncs as u8,
+            self.extra_artist_ids.iter().join("."),
+            parse::encode_url_field(self.external_link.as_deref(), self.raw_external_link.as_deref()),
+            self.new as u8,
+            self.priority.map(|priority| priority.to_string()).unwrap_or_default(),
+            self.new_type.map(|new_type| new_type.to_string()).unwrap_or_default(),
+        ))?;
+
+        self.extra_fields.iter().try_for_each(|field| write!(f, ",{field}"))
     }
 }
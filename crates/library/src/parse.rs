@@ -1,78 +1,243 @@
-use std::{fmt::Display, str::FromStr};
+use std::{borrow::Cow, collections::hash_map::Entry, fmt::Display, hash::Hash, io::Read, str::FromStr};
 
-use ahash::{HashMap, HashMapExt};
-use anyhow::{anyhow, Context};
+use ahash::{HashMap, HashMapExt, HashSet};
+use itertools::Itertools;
 
 use crate::*;
 use crate::sfx::SfxLibraryEntry;
 
-fn parse_semicolon_separated<T: FromStr>(string: &str) -> Vec<T> {
-    string.split(';')
-        .flat_map(T::from_str)
+/// Parses the `;` separated records of a section, skipping empty ones.
+/// Records which can't be parsed are left out and reported in `warnings`.
+fn parse_section<T>(
+    string: &str,
+    section: &'static str,
+    warnings: &mut Vec<LibraryWarning>,
+    parse: impl Fn(&str, &mut Vec<LibraryWarning>) -> Result<T, ParseError>,
+) -> Vec<T> {
+    let mut records = Vec::new();
+
+    for (record, raw) in string.split(';').enumerate() {
+        if raw.is_empty() { continue }
+
+        match parse(raw, warnings) {
+            Ok(value) => records.push(value),
+            Err(source) => warnings.push(LibraryWarning::DroppedRecord { section, record, raw: raw.to_string(), source }),
+        }
+    }
+
+    records
+}
+
+/// Decodes `bytes` as UTF-8, replacing invalid sequences instead of failing.
+fn decode_utf8_lossy(bytes: Vec<u8>, warnings: &mut Vec<LibraryWarning>) -> String {
+    String::from_utf8(bytes).unwrap_or_else(|err| {
+        warnings.push(LibraryWarning::InvalidUtf8 { valid_up_to: err.utf8_error().valid_up_to() });
+        String::from_utf8_lossy(err.as_bytes()).into_owned()
+    })
+}
+
+/// URL-decodes a field, keeping the raw value if it doesn't decode to valid UTF-8.
+fn decode_url_field(
+    value: &str,
+    (section, id, field): (&'static str, EntryId, &'static str),
+    warnings: &mut Vec<LibraryWarning>,
+) -> String {
+    match urlencoding::decode(value) {
+        Ok(decoded) => decoded.into_owned(),
+        Err(_) => {
+            warnings.push(LibraryWarning::InvalidUrlEncoding { section, id, field, value: value.to_string() });
+            value.to_string()
+        }
+    }
+}
+
+/// URL-encodes a field for writing. The escaping of `raw`, the field as it was parsed, is kept if it still decodes to `value`.
+pub(crate) fn encode_url_field<'a>(value: Option<&'a str>, raw: Option<&'a str>) -> Cow<'a, str> {
+    let Some(value) = value else { return Cow::Borrowed("") };
+
+    match raw {
+        Some(raw) if urlencoding::decode(raw).map_or(raw == value, |decoded| decoded == value) => Cow::Borrowed(raw),
+        _ => urlencoding::encode(value),
+    }
+}
+
+/// Splits a record into its first `N` fields and any additional fields added by newer library versions.
+fn split_fields<const N: usize>(string: &str) -> Result<([&str; N], Vec<&str>), ParseError> {
+    let mut fields: Vec<&str> = string.split(',').collect();
+    let found = fields.len();
+    if found < N {
+        return Err(ParseError::FieldCount { expected: N, found })
+    }
+
+    let rest = fields.split_off(N);
+    let fields = fields.try_into().expect("exactly N fields remain");
+    Ok((fields, rest))
+}
+
+fn parse_field<T: FromStr>(field: &'static str, value: &str) -> Result<T, ParseError> {
+    value.parse().map_err(|_| ParseError::InvalidField { field, value: value.to_string() })
+}
+
+/// Like [`parse_field`], but an empty field is `None`.
+fn parse_optional_field<T: FromStr>(field: &'static str, value: &str) -> Result<Option<T>, ParseError> {
+    (!value.is_empty()).then(|| parse_field(field, value)).transpose()
+}
+
+/// Parses a `separator` separated list, which is empty if the field is empty.
+fn parse_list_field<T: FromStr>(field: &'static str, value: &str, separator: char) -> Result<Vec<T>, ParseError> {
+    if value.is_empty() { return Ok(Vec::new()) }
+
+    value.split(separator)
+        .map(|item| parse_field(field, item))
         .collect()
 }
 
 pub(crate) fn parse_sfx_library_from_bytes(bytes: Vec<u8>) -> Result<SfxLibrary> {
-    let bytes = files::encoding::decode(&bytes);
-    let string = std::str::from_utf8(&bytes)?;
+    let bytes = files::encoding::decode(&bytes).map_err(LibraryError::decode)?;
+    let string = std::str::from_utf8(&bytes).map_err(LibraryError::decode)?;
 
     let (library_string, credits_string) = string
         .split_once('|')
         .unwrap_or((string, ""));
 
-    let entries = parse_semicolon_separated(library_string);
-    let credits = parse_semicolon_separated(credits_string);
+    let mut warnings = Vec::new();
+    let entries = parse_section(library_string, "entries", &mut warnings, |string, _| SfxLibraryEntry::from_str(string));
+    let credits = parse_section(credits_string, "credits", &mut warnings, |string, _| sfx::Credit::from_str(string));
 
-    build_sfx_library(entries, credits)
+    build_sfx_library(entries, credits, warnings)
 }
 
 pub(crate) fn parse_music_library_from_bytes(bytes: Vec<u8>) -> Result<MusicLibrary> {
-    let bytes = files::encoding::decode(&bytes);
-    let string = bytes.iter().map(|&byte| char::from(byte)).collect::<String>();
-
-    let [version, credits, songs, tags]: [&str; 4] = string
-        .split('|')
-        .collect::<Vec<&str>>()
+    // the music library is much larger, so avoid keeping an intermediate copy of the compressed data
+    let mut decoded = Vec::with_capacity(bytes.len() * 4);
+    files::encoding::decoder(bytes.as_slice())
+        .read_to_end(&mut decoded)
+        .map_err(LibraryError::decode)?;
+
+    let mut warnings = Vec::new();
+    let string = decode_utf8_lossy(decoded, &mut warnings);
+
+    let sections: Vec<&str> = string.split('|').collect();
+    let section_count = sections.len();
+    let [version, credits, songs, tags]: [&str; 4] = sections
         .try_into()
-        .map_err(|vec| anyhow!("Invalid library entry data: {vec:?}"))?;
+        .map_err(|_| LibraryError::Malformed(format!("expected 4 sections separated by '|', found {section_count}")))?;
+
+    let version = parse_field("version", version)
+        .map_err(|source| LibraryError::Parse { section: "version", record: 0, source })?;
+
+    let credits = parse_section(credits, "credits", &mut warnings, music::Credit::parse);
+    let songs = parse_section(songs, "songs", &mut warnings, music::Song::parse);
+    let tags = parse_section(tags, "tags", &mut warnings, |string, _| music::Tag::from_str(string));
 
     Ok(MusicLibrary {
-        version: version.parse()?,
-        credits: parse_semicolon_separated(credits).into_iter().map(|x: music::Credit| (x.id, x)).collect(),
-        songs: parse_semicolon_separated(songs).into_iter().map(|x: music::Song| (x.id, x)).collect(),
-        tags: parse_semicolon_separated(tags).into_iter().map(|x: music::Tag| (x.id, x)).collect(),
+        version,
+        credits: collect_unique(credits, "credits", |credit| credit.id, &mut warnings),
+        songs: collect_unique(songs, "songs", |song| song.id, &mut warnings),
+        tags: collect_unique(tags, "tags", |tag| tag.id, &mut warnings),
+        warnings,
     })
 }
 
+/// Maps records by their ID. Records whose ID came up before are left out and reported in `warnings`.
+fn collect_unique<K: Copy + Eq + Hash + Into<EntryId>, T>(
+    records: Vec<T>,
+    section: &'static str,
+    id: impl Fn(&T) -> K,
+    warnings: &mut Vec<LibraryWarning>,
+) -> HashMap<K, T> {
+    let mut map = HashMap::with_capacity(records.len());
+
+    for record in records {
+        match map.entry(id(&record)) {
+            Entry::Vacant(entry) => { entry.insert(record); }
+            Entry::Occupied(entry) => warnings.push(LibraryWarning::DuplicateId { section, id: (*entry.key()).into() }),
+        }
+    }
+
+    map
+}
+
+/// Encodes `string` like `sfxlibrary.dat` and parses it.
+#[cfg(test)]
+pub(crate) fn sfx_library_from_str(string: &str) -> SfxLibrary {
+    let bytes = files::encoding::encode(string.as_bytes()).unwrap().into_bytes();
+    parse_sfx_library_from_bytes(bytes).unwrap()
+}
+
+/// Encodes `string` like `musiclibrary.dat` and parses it.
+#[cfg(test)]
+pub(crate) fn music_library_from_str(string: &str) -> MusicLibrary {
+    let bytes = files::encoding::encode(string.as_bytes()).unwrap().into_bytes();
+    parse_music_library_from_bytes(bytes).unwrap()
+}
+
+pub(crate) fn write_sfx_library_to_string(library: &SfxLibrary) -> String {
+    let mut entries = Vec::with_capacity(library.entries.len());
+    let mut written = HashSet::default();
+
+    for id in &library.entry_order {
+        let Some(entry) = library.entries.get(id) else { continue };
+        if written.insert(*id) {
+            entries.push(entry.to_string());
+        }
+    }
+
+    // entries which were added after parsing go last
+    library.entries.values()
+        .filter(|entry| !written.contains(&entry.id))
+        .sorted_by_key(|entry| entry.id)
+        .for_each(|entry| entries.push(entry.to_string()));
+
+    let credits = library.credits.iter().map(ToString::to_string).join(";");
+
+    format!("{}|{credits}", entries.join(";"))
+}
+
+pub(crate) fn write_music_library_to_string(library: &MusicLibrary) -> String {
+    fn join_sorted<K: Ord, V: Display>(map: &HashMap<K, V>) -> String {
+        map.iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, value)| value.to_string())
+            .join(";")
+    }
+
+    format!(
+        "{}|{}|{}|{}",
+        library.version,
+        join_sorted(&library.credits),
+        join_sorted(&library.songs),
+        join_sorted(&library.tags),
+    )
+}
+
 impl sfx::EntryKind {
     const SOUND_KEY: &'static str = "0";
     const CATEGORY_KEY: &'static str = "1";
 }
 
 impl FromStr for SfxLibraryEntry {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
-    fn from_str(string: &str) -> Result<Self, Self::Err> {        
-        let parts @ [id, name, kind, parent_id, ..]: [&str; 6] = string
-            .split(',')
-            .collect::<Vec<_>>()
-            .try_into()
-            .map_err(|vec| anyhow!("Invalid library entry data: {vec:?}"))?;
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let ([id, name, kind, parent_id, bytes, duration], extra_fields) = split_fields(string)?;
 
         let entry = Self {
-            id: id.parse()?,
+            id: parse_field("id", id)?,
             name: name.to_string(),
-            parent_id: parent_id.parse()?,
+            parent_id: parse_field("parent_id", parent_id)?,
 
             kind: match kind {
                 sfx::EntryKind::SOUND_KEY => sfx::EntryKind::Sound {
-                    bytes: parts[4].parse()?,
-                    duration: Duration::from_millis(10 * parts[5].parse::<u64>()?),
+                    bytes: parse_field("bytes", bytes)?,
+                    duration: Duration::from_millis(10 * parse_field::<u64>("duration", duration)?),
                 },
                 sfx::EntryKind::CATEGORY_KEY => sfx::EntryKind::Category,
 
-                _ => anyhow::bail!("Unknown library entry type")
-            }
+                _ => return Err(ParseError::InvalidField { field: "kind", value: kind.to_string() }),
+            },
+
+            extra_fields: extra_fields.into_iter().map(str::to_string).collect(),
         };
 
         Ok(entry)
@@ -100,105 +265,156 @@ impl Display for SfxLibraryEntry {
             (duration.as_millis() / 10).to_string(),
         ]
         .join(",");
-        
-        f.write_str(&parts)
+
+        f.write_str(&parts)?;
+        self.extra_fields.iter().try_for_each(|field| write!(f, ",{field}"))
     }
 }
 
 impl FromStr for sfx::Credit {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        string
-            .split_once(',')
-            .map(|(name, link)| Self {
-                name: name.to_string(),
-                link: link.to_string(),
-            })
-            .ok_or(anyhow!("Credits must have format \"name,link\", found {string}"))
+        // links can contain commas, so everything after the name is part of the link
+        let (name, link) = string.split_once(',')
+            .ok_or(ParseError::FieldCount { expected: 2, found: 1 })?;
+
+        Ok(Self {
+            name: name.to_string(),
+            link: link.to_string(),
+        })
+    }
+}
+
+impl Display for sfx::Credit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.name, self.link)
+    }
+}
+
+impl music::Credit {
+    const YOUTUBE_CHANNEL_URL: &'static str = "https://youtube.com/channel/";
+}
+
+impl music::Credit {
+    fn parse(string: &str, warnings: &mut Vec<LibraryWarning>) -> Result<Self, ParseError> {
+        let ([id, name, url, yt_channel_id], extra_fields) = split_fields(string)?;
+        let id = parse_field("id", id.trim())?;
+        let url = url.trim();
+
+        Ok(Self {
+            id,
+            name: name.trim().to_string(),
+            url: (!url.is_empty()).then(|| decode_url_field(url, ("credit", id, "url"), warnings)),
+            yt_url: {
+                let yt_channel_id = yt_channel_id.trim();
+                (!yt_channel_id.is_empty()).then(|| format!("{}{yt_channel_id}", Self::YOUTUBE_CHANNEL_URL))
+            },
+            raw_url: (!url.is_empty()).then(|| url.to_string()),
+            extra_fields: extra_fields.into_iter().map(str::to_string).collect(),
+        })
     }
 }
 
 impl FromStr for music::Credit {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        string
-            .split(',')
-            .collect::<Vec<&str>>()
-            .try_into()
-            .map(|[id, name, url, yt_channel_id]: [&str; 4]| Self {
-                id: id.trim().parse().unwrap_or(0),
-                name: name.trim().to_string(),
-                url: {
-                    let url = url.trim();
-                    if url.is_empty() {
-                        None
-                    } else {
-                        urlencoding::decode(url)
-                            .map(|url| url.to_string())
-                            .ok()
-                    }
-                },
-                yt_url: {
-                    let yt_channel_id = yt_channel_id.trim();
-                    (!yt_channel_id.is_empty()).then_some(format!("https://youtube.com/channel/{yt_channel_id}"))
-                },
-            })
-            .ok()
-            .ok_or(anyhow!("Credits must have format \"id,name,url,yt_channel_id\", found {string}"))
+        Self::parse(string, &mut Vec::new())
+    }
+}
+
+impl Display for music::Credit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let url = encode_url_field(self.url.as_deref(), self.raw_url.as_deref());
+        let yt_channel_id = self.yt_url.as_deref()
+            .map(|url| url.strip_prefix(Self::YOUTUBE_CHANNEL_URL).unwrap_or(url))
+            .unwrap_or_default();
+
+        write!(f, "{},{},{url},{yt_channel_id}", self.id, self.name)?;
+        self.extra_fields.iter().try_for_each(|field| write!(f, ",{field}"))
+    }
+}
+
+impl music::Song {
+    fn parse(string: &str, warnings: &mut Vec<LibraryWarning>) -> Result<Self, ParseError> {
+        let ([id, name, credit_id, bytes, duration, tags, ncs, extra_artist_ids, external_link, new, priority, new_type], extra_fields) =
+            split_fields(string)?;
+        let id = parse_field("id", id)?;
+
+        Ok(Self {
+            id,
+            name: name.to_string(),
+            credit_id: parse_field("credit_id", credit_id)?,
+            bytes: parse_field("bytes", bytes)?,
+            duration: Duration::from_secs(parse_field("duration", duration)?),
+            // tags are surrounded by dots, e.g. `.1.2.`
+            tags: tags.split('.')
+                .filter(|tag| !tag.is_empty())
+                .map(|tag| parse_field("tags", tag))
+                .collect::<Result<_, _>>()?,
+            ncs: ncs == "1",
+            extra_artist_ids: parse_list_field("extra_artist_ids", extra_artist_ids, '.')?,
+            external_link: (!external_link.is_empty())
+                .then(|| decode_url_field(external_link, ("song", id, "external link"), warnings)),
+            raw_external_link: (!external_link.is_empty()).then(|| external_link.to_string()),
+            new: new == "1",
+            priority: parse_optional_field("priority", priority)?,
+            new_type: parse_optional_field("new_type", new_type)?,
+            extra_fields: extra_fields.into_iter().map(str::to_string).collect(),
+        })
     }
 }
 
 impl FromStr for music::Song {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        string
-            .split(',')
-            .collect::<Vec<&str>>()
-            .try_into()
-            .map(|[id, name, credit_id, bytes, duration, tags, ncs, unk2, url, new, unk4, unk5 ]: [&str; 12]| {
-                let song = Self {
-                    id: id.parse().unwrap_or(0),
-                    name: name.to_string(),
-                    credit_id: credit_id.parse().unwrap_or(0),
-                    bytes: bytes.parse().unwrap_or(0),
-                    duration: Duration::from_secs(duration.parse().unwrap_or(0)),
-                    tags: tags.split('.').filter_map(|s| s.parse().ok()).collect(),
-                    ncs: ncs == "1",
-                    unk2: unk2.to_string(),
-                    url: url.to_string(),
-                    new: new == "1",
-                    unk4: unk4.to_string(),
-                    unk5: unk5.to_string(),
-                };
-
-                song
-            })
-            .ok()
-            .ok_or(anyhow!("Songs must have format \"id,name,credit_id,bytes,duration,tags,ncs,unk2,url,new,unk4,unk5\", found {string}"))
+        Self::parse(string, &mut Vec::new())
     }
 }
 
 impl FromStr for music::Tag {
-    type Err = anyhow::Error;
+    type Err = ParseError;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        string
-            .split_once(',')
-            .map(|(id, name)| Self {
-                id: id.parse().unwrap_or(0),
-                name: name.to_string(),
-            })
-            .ok_or(anyhow!("Tags must have format \"id,name\", found {string}"))
+        let ([id, name], extra_fields) = split_fields(string)?;
+
+        Ok(Self {
+            id: parse_field("id", id)?,
+            name: name.to_string(),
+            extra_fields: extra_fields.into_iter().map(str::to_string).collect(),
+        })
     }
 }
 
-fn build_sfx_library(entries: Vec<SfxLibraryEntry>, credits: Vec<sfx::Credit>) -> Result<SfxLibrary> {
-    // TODO: can the root id be (reasonably) evaluated programatically?
-    let root_id = entries.first().context("No library entries")?.id;
+impl Display for music::Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.id, self.name)?;
+        self.extra_fields.iter().try_for_each(|field| write!(f, ",{field}"))
+    }
+}
+
+fn build_sfx_library(
+    entries: Vec<SfxLibraryEntry>,
+    credits: Vec<sfx::Credit>,
+    mut warnings: Vec<LibraryWarning>,
+) -> Result<SfxLibrary> {
+    let mut ids = HashSet::default();
+    let entries = entries.into_iter()
+        .filter(|entry| {
+            let is_new = ids.insert(entry.id);
+            if !is_new {
+                warnings.push(LibraryWarning::DuplicateId { section: "entries", id: entry.id });
+            }
+            is_new
+        })
+        .collect_vec();
+
+    let root_id = find_root_id(&entries, &ids)
+        .ok_or_else(|| LibraryError::Malformed("no library entries".to_string()))?;
     let mut sound_ids = Vec::new();
+    let entry_order = entries.iter().map(|entry| entry.id).collect();
 
     let mut entry_map = HashMap::new();
     let mut child_map = HashMap::new();
@@ -213,10 +429,13 @@ fn build_sfx_library(entries: Vec<SfxLibraryEntry>, credits: Vec<sfx::Credit>) -
 
             sound_ids.push(entry.id);
         }
-        
-        child_map.entry(entry.parent_id)
-            .or_insert(Vec::new())
-            .push(entry.id);
+
+        // the root is never a child, so traversing from it can't loop
+        if entry.id != root_id {
+            child_map.entry(entry.parent_id)
+                .or_insert(Vec::new())
+                .push(entry.id);
+        }
 
         entry_map.insert(entry.id, entry);
     }
@@ -224,6 +443,7 @@ fn build_sfx_library(entries: Vec<SfxLibraryEntry>, credits: Vec<sfx::Credit>) -
     Ok(SfxLibrary {
         root_id,
         sound_ids,
+        entry_order,
 
         entries: entry_map,
         child_map,
@@ -232,9 +452,26 @@ fn build_sfx_library(entries: Vec<SfxLibraryEntry>, credits: Vec<sfx::Credit>) -
         total_duration,
 
         credits,
+        warnings,
     })
 }
 
+/// GD lists the root first, so the first entry is the root if it's a category whose parent isn't part of the library.
+/// Otherwise the first such category is used, or the first entry if there's none, e.g. because every entry is part of a cycle.
+/// Other categories without a parent are reported by [`SfxLibrary::validate`].
+fn find_root_id(entries: &[SfxLibraryEntry], ids: &HashSet<EntryId>) -> Option<EntryId> {
+    let is_root = |entry: &&SfxLibraryEntry| {
+        matches!(entry.kind, sfx::EntryKind::Category)
+            && (entry.parent_id == entry.id || !ids.contains(&entry.parent_id))
+    };
+
+    entries.first()
+        .filter(is_root)
+        .or_else(|| entries.iter().find(is_root))
+        .or(entries.first())
+        .map(|entry| entry.id)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -251,13 +488,230 @@ mod test {
             kind: sfx::EntryKind::Sound {
                 bytes: 29496,
                 duration: Duration::from_millis(187 * 10),
-            }
+            },
+            extra_fields: Vec::new(),
         });
 
         let string = entry.to_string();
         assert_eq!(string, FIRE_IN_THE_HOLE);
     }
 
+    #[test]
+    fn test_parse_library_entry_errors() {
+        assert_eq!(
+            SfxLibraryEntry::from_str("4451,Fire In The Hole,0,4442"),
+            Err(ParseError::FieldCount { expected: 6, found: 4 }),
+        );
+        assert_eq!(
+            SfxLibraryEntry::from_str("4451,Fire In The Hole,0,4442,big,187"),
+            Err(ParseError::InvalidField { field: "bytes", value: "big".to_string() }),
+        );
+        assert_eq!(
+            SfxLibraryEntry::from_str("4451,Fire In The Hole,7,4442,29496,187"),
+            Err(ParseError::InvalidField { field: "kind", value: "7".to_string() }),
+        );
+    }
+
+    #[test]
+    fn test_sfx_library_tolerates_errors() {
+        const LIBRARY: &str = "1,v1,1,0,0,0;2,Explosions,1,1,0,0,future;4451,Fire In The Hole,0,2,big,187;4452,Boom;;4453,Bang,0,2,1000,50,a,b|Sharks,https://www.sharkstunes.com";
+
+        let bytes = files::encoding::encode(LIBRARY.as_bytes()).unwrap().into_bytes();
+        let library = parse_sfx_library_from_bytes(bytes).unwrap();
+
+        assert_eq!(library.entries[&2].extra_fields, vec!["future".to_string()]);
+        assert_eq!(library.entries[&4453].extra_fields, vec!["a".to_string(), "b".to_string()]);
+        assert!(!library.entries.contains_key(&4451));
+        assert!(!library.entries.contains_key(&4452));
+
+        assert_eq!(library.dropped_records(), 2);
+        assert_eq!(library.warnings[0], LibraryWarning::DroppedRecord {
+            section: "entries",
+            record: 2,
+            raw: "4451,Fire In The Hole,0,2,big,187".to_string(),
+            source: ParseError::InvalidField { field: "bytes", value: "big".to_string() },
+        });
+        assert!(matches!(library.warnings[1], LibraryWarning::DroppedRecord { record: 3, source: ParseError::FieldCount { .. }, .. }));
+
+        // extra fields are written back unchanged
+        let serialized = files::encoding::decode(&library.to_bytes().unwrap()).unwrap();
+        assert!(String::from_utf8(serialized).unwrap().contains("4453,Bang,0,2,1000,50,a,b"));
+    }
+
+    #[test]
+    fn test_sfx_library_round_trip() {
+        const LIBRARY: &str = "1,v1,1,0,0,0;2,Explosions,1,1,0,0;4451,Fire In The Hole,0,2,29496,187;4452,Boom,0,2,1000,50;3,Empty,1,1,0,0\
+            |Sharks,https://www.sharkstunes.com;Someone,https://example.com/a,b";
+
+        let bytes = files::encoding::encode(LIBRARY.as_bytes()).unwrap().into_bytes();
+        let library = parse_sfx_library_from_bytes(bytes).unwrap();
+
+        let serialized = library.to_bytes().unwrap();
+        assert_eq!(files::encoding::decode(&serialized).unwrap(), LIBRARY.as_bytes());
+        assert_eq!(parse_sfx_library_from_bytes(serialized).unwrap(), library);
+    }
+
+    #[test]
+    fn test_library_files_round_trip() {
+        const SFX_LIBRARY: &[u8] = include_bytes!("../fixtures/sfxlibrary.dat");
+        const MUSIC_LIBRARY: &[u8] = include_bytes!("../fixtures/musiclibrary.dat");
+
+        // the compressed data depends on the zlib implementation, so the decoded contents are compared
+        let decode = |bytes: &[u8]| files::encoding::decode(bytes).unwrap();
+
+        let sfx_library = parse_sfx_library_from_bytes(SFX_LIBRARY.to_vec()).unwrap();
+        assert!(sfx_library.warnings.is_empty());
+        assert_eq!(decode(&sfx_library.to_bytes().unwrap()), decode(SFX_LIBRARY));
+
+        let music_library = parse_music_library_from_bytes(MUSIC_LIBRARY.to_vec()).unwrap();
+        assert!(music_library.warnings.is_empty());
+        assert_eq!(music_library.credits[&2].url.as_deref(), Some("https://f777.bandcamp.com"));
+        assert_eq!(decode(&music_library.to_bytes().unwrap()), decode(MUSIC_LIBRARY));
+    }
+
+    #[test]
+    fn test_music_library_round_trip() {
+        const LIBRARY: &str = "42\
+            |1,Waterflame,https%3A%2F%2Fwaterflame.com,UCVuv5iaVR55QXIc_BHQLakA;2,Someone,,\
+            |10000001,Glorious Morning,1,1500000,120,.1.2.,1,,,0,,;10000002,Song,2,2000,5,.2.,0,,,1,,\
+            ;10000003,Featuring,1,3000,10,.1.,1,2.3,https%3A%2F%2Fncs.io%2Ffeaturing,1,5,2,y\
+            |1,Action;2,Calm";
+
+        let bytes = files::encoding::encode(LIBRARY.as_bytes()).unwrap().into_bytes();
+        let library = parse_music_library_from_bytes(bytes).unwrap();
+
+        let credit = &library.credits[&1];
+        assert_eq!(credit.url.as_deref(), Some("https://waterflame.com"));
+        assert_eq!(credit.yt_url.as_deref(), Some("https://youtube.com/channel/UCVuv5iaVR55QXIc_BHQLakA"));
+
+        let song = &library.songs[&10000003];
+        assert_eq!(song.artist_ids().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(song.external_link.as_deref(), Some("https://ncs.io/featuring"));
+        assert_eq!(song.priority, Some(5));
+        assert_eq!(song.new_type, Some(2));
+        assert_eq!(song.extra_fields, vec!["y".to_string()]);
+        assert_eq!(library.songs[&10000001].priority, None);
+        assert_eq!(
+            music::Song::from_str("10000004,Song,1,1000,60,.1.,0,,,0,high,"),
+            Err(ParseError::InvalidField { field: "priority", value: "high".to_string() }),
+        );
+        assert_eq!(
+            music::Song::from_str("10000004,Song,1,1000,60,.1.,0,2.x,,0,,"),
+            Err(ParseError::InvalidField { field: "extra_artist_ids", value: "x".to_string() }),
+        );
+        assert_eq!(
+            music::Song::from_str("10000004,Song,1,1000,60,.1.action.,0,,,0,,"),
+            Err(ParseError::InvalidField { field: "tags", value: "action".to_string() }),
+        );
+        assert_eq!(music::Song::from_str("10000004,Song,1,1000,60,,0,,,0,,").unwrap().tags, Vec::<music::TagId>::new());
+
+        let serialized = library.to_bytes().unwrap();
+        assert_eq!(files::encoding::decode(&serialized).unwrap(), LIBRARY.as_bytes());
+        assert_eq!(parse_music_library_from_bytes(serialized).unwrap(), library);
+    }
+
+    #[test]
+    fn test_music_library_tolerates_errors() {
+        const LIBRARY: &str = "42\
+            |1,Waterflame,,,future;x,Nobody,,\
+            |10000001,Song,1,1500000,120,.1.,1,,,0,,;10000002,Bad Size,1,big,120,.1.,1,,,0,,;,No ID,1,1000,60,.1.,0,,,0,,\
+            |1,Action,future;2";
+
+        let bytes = files::encoding::encode(LIBRARY.as_bytes()).unwrap().into_bytes();
+        let library = parse_music_library_from_bytes(bytes).unwrap();
+
+        assert_eq!(library.credits.len(), 1);
+        assert_eq!(library.credits[&1].extra_fields, vec!["future".to_string()]);
+        assert_eq!(library.songs.len(), 1);
+        assert!(!library.songs.contains_key(&0));
+        assert_eq!(library.tags[&1].extra_fields, vec!["future".to_string()]);
+
+        assert_eq!(library.dropped_records(), 4);
+        assert_eq!(library.warnings[0], LibraryWarning::DroppedRecord {
+            section: "credits",
+            record: 1,
+            raw: "x,Nobody,,".to_string(),
+            source: ParseError::InvalidField { field: "id", value: "x".to_string() },
+        });
+        assert!(matches!(library.warnings[1], LibraryWarning::DroppedRecord {
+            section: "songs", record: 1, source: ParseError::InvalidField { field: "bytes", .. }, ..
+        }));
+        assert!(matches!(library.warnings[2], LibraryWarning::DroppedRecord {
+            section: "songs", record: 2, source: ParseError::InvalidField { field: "id", .. }, ..
+        }));
+        assert!(matches!(library.warnings[3], LibraryWarning::DroppedRecord {
+            section: "tags", record: 1, source: ParseError::FieldCount { expected: 2, found: 1 }, ..
+        }));
+
+        // extra fields are written back unchanged
+        let serialized = String::from_utf8(files::encoding::decode(&library.to_bytes().unwrap()).unwrap()).unwrap();
+        assert!(serialized.contains("1,Waterflame,,,future"));
+        assert!(serialized.contains("1,Action,future"));
+    }
+
+    #[test]
+    fn test_music_library_duplicate_ids() {
+        const LIBRARY: &str = "42\
+            |1,Waterflame,,;1,Impostor,,\
+            |10000001,Song,1,1000,60,.1.,0,,,0,,;10000001,Other Song,1,1000,60,.1.,0,,,0,,\
+            |1,Action;1,Calm";
+
+        let library = music_library_from_str(LIBRARY);
+
+        // the first record wins
+        assert_eq!(library.credits[&1].name, "Waterflame");
+        assert_eq!(library.songs[&10000001].name, "Song");
+        assert_eq!(library.tags[&1].name, "Action");
+
+        assert_eq!(library.dropped_records(), 3);
+        assert_eq!(library.warnings, vec![
+            LibraryWarning::DuplicateId { section: "credits", id: 1 },
+            LibraryWarning::DuplicateId { section: "songs", id: 10000001 },
+            LibraryWarning::DuplicateId { section: "tags", id: 1 },
+        ]);
+    }
+
+    #[test]
+    fn test_music_library_non_latin_names() {
+        const LIBRARY: &str = "7\
+            |1,Сергей Петров,https%3A%2F%2Fexample.com%2F%E6%97%A5%E6%9C%AC,;2,Ñandú,,\
+            |10000001,日本の歌,1,1000,60,.1.,0,,https%3A%2F%2Fexample.com%2F%C3%B1,0,,;10000002,Ωmega ★,2,1000,60,.1.,0,,,0,,\
+            |1,Música";
+
+        let bytes = files::encoding::encode(LIBRARY.as_bytes()).unwrap().into_bytes();
+        let library = parse_music_library_from_bytes(bytes).unwrap();
+
+        assert!(library.warnings.is_empty());
+        assert_eq!(library.credits[&1].name, "Сергей Петров");
+        assert_eq!(library.credits[&1].url.as_deref(), Some("https://example.com/日本"));
+        assert_eq!(library.credits[&2].name, "Ñandú");
+        assert_eq!(library.songs[&10000001].name, "日本の歌");
+        assert_eq!(library.songs[&10000001].external_link.as_deref(), Some("https://example.com/ñ"));
+        assert_eq!(library.songs[&10000002].name, "Ωmega ★");
+        assert_eq!(library.tags[&1].name, "Música");
+
+        let serialized = library.to_bytes().unwrap();
+        assert_eq!(files::encoding::decode(&serialized).unwrap(), LIBRARY.as_bytes());
+    }
+
+    #[test]
+    fn test_music_library_invalid_utf8() {
+        let mut data = b"1||10000001,Bad ".to_vec();
+        data.extend_from_slice(&[0xff, 0xfe]);
+        data.extend_from_slice(b" Name,1,1000,60,.1.,0,,%FF,0,,|");
+
+        let bytes = files::encoding::encode(&data).unwrap().into_bytes();
+        let library = parse_music_library_from_bytes(bytes).unwrap();
+
+        let song = &library.songs[&10000001];
+        assert_eq!(song.name, "Bad \u{FFFD}\u{FFFD} Name");
+        assert_eq!(song.external_link.as_deref(), Some("%FF"));
+        assert_eq!(library.warnings, vec![
+            LibraryWarning::InvalidUtf8 { valid_up_to: 16 },
+            LibraryWarning::InvalidUrlEncoding { section: "song", id: 10000001, field: "external link", value: "%FF".to_string() },
+        ]);
+    }
+
     #[test]
     fn test_parse_credit() {
         const SHARKS_CREDIT: &str = "Sharks,https://www.sharkstunes.com";
@@ -267,5 +721,8 @@ mod test {
             name: "Sharks".to_string(),
             link: "https://www.sharkstunes.com".to_string(),
         });
+
+        let credit = sfx::Credit::from_str("Someone,https://example.com/a,b").unwrap();
+        assert_eq!(credit.link, "https://example.com/a,b");
     }
 }
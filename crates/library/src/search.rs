@@ -0,0 +1,336 @@
+use std::{cmp::Ordering, collections::BTreeMap, ops::Bound};
+
+use ahash::{HashMap, HashMapExt};
+use itertools::Itertools;
+
+use crate::*;
+use crate::sfx::EntryKind;
+
+/// Where a token of an indexed entry comes from. Matches in more specific fields rank higher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    Name,
+    /// Names of the categories containing a sound.
+    Path,
+    Artist,
+    Tag,
+}
+
+impl Field {
+    fn weight(self) -> f32 {
+        match self {
+            Self::Id => 1.5,
+            Self::Name => 1.0,
+            Self::Artist => 0.7,
+            Self::Tag => 0.6,
+            Self::Path => 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchResult {
+    pub id: EntryId,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Posting {
+    document: usize,
+    field: Field,
+}
+
+#[derive(Debug)]
+struct Document {
+    id: EntryId,
+    /// Normalized name, used to rank names containing the whole query higher.
+    name: String,
+}
+
+/// Typo-tolerant full-text index over the sounds of an [`SfxLibrary`] or the songs of a [`MusicLibrary`].
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    documents: Vec<Document>,
+    /// Sorted, so that words starting with a prefix can be found without going through all of them.
+    vocabulary: BTreeMap<String, Vec<Posting>>,
+}
+
+impl SearchIndex {
+    /// Indexes the name, category path and ID of every sound.
+    pub fn for_sfx(library: &SfxLibrary) -> Self {
+        let mut index = Self::default();
+
+        for entry in library.entries.values().sorted_by_key(|entry| entry.id) {
+            if matches!(entry.kind, EntryKind::Category) { continue }
+
+            index.add(entry.id, &entry.name, [(Field::Path, library.path_string(entry.parent_id))]);
+        }
+
+        index
+    }
+
+    /// Indexes the name, artist names, tag names and ID of every song.
+    pub fn for_music(library: &MusicLibrary) -> Self {
+        let mut index = Self::default();
+
+        for song in library.songs.values().sorted_by_key(|song| song.id) {
+            let artists = song.artist_ids()
+                .flat_map(|id| library.credits.get(&id))
+                .map(|credit| (Field::Artist, credit.name.clone()));
+            let tags = song.tags.iter()
+                .flat_map(|id| library.tags.get(id))
+                .map(|tag| (Field::Tag, tag.name.clone()));
+
+            index.add(song.id, &song.name, artists.chain(tags));
+        }
+
+        index
+    }
+
+    fn add(&mut self, id: EntryId, name: &str, fields: impl IntoIterator<Item = (Field, String)>) {
+        let document = self.documents.len();
+        self.documents.push(Document { id, name: normalize(name) });
+
+        let fields = [(Field::Id, id.to_string()), (Field::Name, name.to_string())].into_iter().chain(fields);
+        for (field, text) in fields {
+            for token in tokenize(&text) {
+                self.vocabulary.entry(token).or_default().push(Posting { document, field });
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Finds entries matching every word of `query`, best matches first.
+    /// Words match exactly, as a prefix of a word or with a few typos depending on their length.
+    /// Words which don't match any of these ways also match in the middle of a word.
+    pub fn search(&self, query: &str) -> Vec<SearchResult> {
+        let query_tokens = tokenize(query).collect_vec();
+        if query_tokens.is_empty() {
+            return Vec::new()
+        }
+
+        let mut scores: Option<HashMap<usize, f32>> = None;
+
+        for query_token in &query_tokens {
+            let mut token_scores = HashMap::new();
+
+            for (postings, similarity) in self.matching_tokens(query_token) {
+                for posting in postings {
+                    if posting.field == Field::Id && similarity < 1.0 { continue }
+
+                    let score = similarity * posting.field.weight();
+                    let best = token_scores.entry(posting.document).or_insert(0.0);
+                    *best = f32::max(*best, score);
+                }
+            }
+
+            // every word of the query has to match
+            scores = Some(match scores {
+                None => token_scores,
+                Some(scores) => scores.into_iter()
+                    .filter_map(|(document, score)| token_scores.get(&document).map(|token_score| (document, score + token_score)))
+                    .collect(),
+            });
+        }
+
+        let phrase = normalize(query);
+
+        scores.unwrap_or_default().into_iter()
+            .map(|(document, score)| {
+                let document = &self.documents[document];
+                let bonus = if document.name.contains(&phrase) { 0.5 } else { 0.0 };
+                SearchResult { id: document.id, score: score + bonus }
+            })
+            .sorted_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal).then(a.id.cmp(&b.id)))
+            .collect()
+    }
+}
+
+impl SearchIndex {
+    /// Postings of the indexed words matching `query_token` and how well they match, from 0 to 1.
+    fn matching_tokens<'a>(&'a self, query_token: &'a str) -> Vec<(&'a [Posting], f32)> {
+        let mut matches = Vec::new();
+
+        for (token, postings) in self.words_starting_with(query_token) {
+            let similarity = if token == query_token { 1.0 } else { 0.75 };
+            matches.push((postings.as_slice(), similarity));
+        }
+
+        // only words with the same first character are checked for typos, which keeps searching while typing fast
+        if let Some(max_distance) = max_typos(query_token) {
+            let first_character = &query_token[..query_token.chars().next().map_or(0, char::len_utf8)];
+
+            for (token, postings) in self.words_starting_with(first_character) {
+                if token.starts_with(query_token) { continue }
+                let Some(similarity) = typo_similarity(query_token, token, max_distance) else { continue };
+                matches.push((postings.as_slice(), similarity));
+            }
+        }
+
+        if matches.is_empty() && query_token.chars().count() >= MIN_SUBSTRING_LENGTH {
+            for (token, postings) in &self.vocabulary {
+                if token.contains(query_token) {
+                    matches.push((postings.as_slice(), 0.4));
+                }
+            }
+        }
+
+        matches
+    }
+
+    fn words_starting_with<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a String, &'a Vec<Posting>)> + 'a {
+        self.vocabulary.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |(token, _)| token.starts_with(prefix))
+    }
+}
+
+/// Shorter query words only match at the start of a word, since they would match too many words otherwise.
+const MIN_SUBSTRING_LENGTH: usize = 3;
+
+fn normalize(text: &str) -> String {
+    tokenize(text).join(" ")
+}
+
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Number of typos allowed in a query token, depending on its length.
+fn max_typos(query_token: &str) -> Option<usize> {
+    match query_token.chars().count() {
+        0..=3 => None,
+        4..=7 => Some(1),
+        _ => Some(2),
+    }
+}
+
+/// How well a query token matches an indexed token with up to `max_distance` typos, from 0 to 1.
+fn typo_similarity(query_token: &str, token: &str, max_distance: usize) -> Option<f32> {
+    if token.chars().count().abs_diff(query_token.chars().count()) > max_distance {
+        return None
+    }
+
+    match edit_distance(query_token, token) {
+        1 => Some(0.5),
+        2 if max_distance >= 2 => Some(0.35),
+        _ => None,
+    }
+}
+
+/// Levenshtein distance which counts swapping two adjacent characters as a single edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect_vec();
+    let b = b.chars().collect_vec();
+
+    let mut previous_previous = vec![0; b.len() + 1];
+    let mut previous = (0..=b.len()).collect_vec();
+    let mut current = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(previous_previous[j - 2] + 1);
+            }
+        }
+        previous_previous.clone_from(&previous);
+        previous.clone_from(&current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sfx_library() -> SfxLibrary {
+        parse::sfx_library_from_str("1,v1,1,0,0,0;2,Explosions,1,1,0,0;3,Voices,1,1,0,0;\
+            4451,Fire In The Hole,0,3,29496,187;4452,Big Explosion,0,2,1000,50;4453,Firework,0,2,1000,50;4454,Hole Punch,0,3,100,10")
+    }
+
+    fn ids(results: Vec<SearchResult>) -> Vec<EntryId> {
+        results.into_iter().map(|result| result.id).collect()
+    }
+
+    #[test]
+    fn test_search_words() {
+        let index = SearchIndex::for_sfx(&sfx_library());
+
+        assert_eq!(ids(index.search("fire in hole")), vec![4451]);
+        assert_eq!(ids(index.search("FIRE, HOLE!")), vec![4451]);
+        assert_eq!(ids(index.search("")), Vec::<EntryId>::new());
+    }
+
+    #[test]
+    fn test_search_ranking() {
+        let index = SearchIndex::for_sfx(&sfx_library());
+
+        // exact word before prefix
+        assert_eq!(ids(index.search("fire")), vec![4451, 4453]);
+        // the sound's own name before its category
+        assert_eq!(ids(index.search("explosion")), vec![4452, 4453]);
+        // category paths are searchable
+        assert_eq!(ids(index.search("voices hole")), vec![4451, 4454]);
+        // IDs only match exactly
+        assert_eq!(ids(index.search("4451")), vec![4451]);
+    }
+
+    #[test]
+    fn test_search_typos() {
+        let index = SearchIndex::for_sfx(&sfx_library());
+
+        assert_eq!(ids(index.search("fier in the hoel")), vec![4451]);
+        assert_eq!(ids(index.search("explsoion")), vec![4452, 4453]);
+        // words of four to seven characters may have one typo
+        assert_eq!(ids(index.search("hoke")), vec![4451, 4454]);
+        // shorter words may not have any
+        assert_eq!(ids(index.search("teh")), Vec::<EntryId>::new());
+    }
+
+    #[test]
+    fn test_search_substrings() {
+        let index = SearchIndex::for_sfx(&sfx_library());
+
+        assert_eq!(ids(index.search("plosion")), vec![4452, 4453]);
+        assert_eq!(ids(index.search("work")), vec![4453]);
+        assert_eq!(ids(index.search("ole")), vec![4451, 4454]);
+        // shorter words would match too much
+        assert_eq!(ids(index.search("le")), Vec::<EntryId>::new());
+    }
+
+    #[test]
+    fn test_search_music() {
+        const LIBRARY: &str = "1|1,Waterflame,,;2,F-777,,|10000001,Glorious Morning,1,1000,60,.1.,0,2,,0,,;10000002,Ricochet Love,2,1000,60,.2.,0,,,0,,|1,Action;2,Calm";
+        let index = SearchIndex::for_music(&parse::music_library_from_str(LIBRARY));
+
+        assert_eq!(ids(index.search("waterflame")), vec![10000001]);
+        // featured artists are included
+        assert_eq!(ids(index.search("f 777")), vec![10000001, 10000002]);
+        assert_eq!(ids(index.search("calm love")), vec![10000002]);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("hole", "hole"), 0);
+        assert_eq!(edit_distance("hole", "hold"), 1);
+        assert_eq!(edit_distance("teh", "the"), 1);
+        assert_eq!(edit_distance("explosion", "explsoion"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -1,60 +1,118 @@
-use std::path::Path;
+use std::{fs, path::Path};
 use std::time::Duration;
 
-use anyhow::Result;
 use ahash::HashMap;
+use serde::Serialize;
 
 use crate::*;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct SfxLibraryEntry {
     pub id: EntryId,
     pub name: String,
     pub parent_id: EntryId,
+    #[serde(flatten)]
     pub kind: EntryKind,
+    /// Fields after the known ones, kept so that they are written back unchanged.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_fields: Vec<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum EntryKind {
     Category,
-    Sound { bytes: BytesSize, duration: Duration },
+    Sound {
+        bytes: BytesSize,
+        #[serde(serialize_with = "export::serialize_duration")]
+        duration: Duration,
+    },
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct Credit {
     pub name: String,
     pub link: String,
 }
 
+impl Default for SfxLibrary {
+    /// An empty library which only consists of its root category.
+    fn default() -> Self {
+        let root = SfxLibraryEntry {
+            id: 0,
+            name: String::new(),
+            parent_id: 0,
+            kind: EntryKind::Category,
+            extra_fields: Vec::new(),
+        };
+
+        Self {
+            root_id: root.id,
+            sound_ids: Vec::new(),
+            entry_order: vec![root.id],
+            entries: [(root.id, root)].into_iter().collect(),
+            child_map: HashMap::default(),
+            total_bytes: 0,
+            total_duration: Duration::ZERO,
+            credits: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
 impl SfxLibrary {
-    pub fn load(gd_folder: impl AsRef<Path>) -> Result<Self> {
+    pub fn load(gd_folder: impl AsRef<Path>, source: &dyn ContentSource) -> Result<Self> {
+        Self::load_with_previous(gd_folder, source).map(|(library, _)| library)
+    }
+
+    /// Like [`Self::load`], but also returns the local library if it was replaced by an update.
+    pub fn load_with_previous(gd_folder: impl AsRef<Path>, source: &dyn ContentSource) -> Result<(Self, Option<Self>)> {
         const SFX_LIBRARY_FILE: &str = "sfxlibrary.dat";
 
         let file = gd_folder.as_ref().join(SFX_LIBRARY_FILE);
 
-        let local_library = files::read_file(&file)
+        let local_library = fs::read(&file)
+            .map_err(LibraryError::io(&file))
             .and_then(parse::parse_sfx_library_from_bytes);
 
-        if !Self::should_try_update(local_library.as_ref().ok()) {
+        if source.is_offline() {
             return local_library
+                .map(|library| (library, None))
+                .map_err(|source| LibraryError::OfflineLibraryUnavailable {
+                    path: file,
+                    source: Box::new(source),
+                })
         }
 
-        requests::request_sfx_file(SFX_LIBRARY_FILE)
-            .and_then(|response| {
-                let bytes = response.bytes()?.to_vec();
-                let _ = files::write_file(&file, &bytes);
-                parse::parse_sfx_library_from_bytes(bytes)
-            })
-            .or_else(|download_err| local_library.map_err(|_| download_err))
+        if !Self::should_try_update(local_library.as_ref().ok(), source) {
+            return local_library.map(|library| (library, None))
+        }
+
+        match source.fetch_sfx_file(SFX_LIBRARY_FILE).and_then(|bytes| {
+            let library = parse::parse_sfx_library_from_bytes(bytes.clone())?;
+            let _ = fs::write(&file, &bytes);
+            Ok(library)
+        }) {
+            Ok(library) => Ok((library, local_library.ok())),
+            Err(download_err) => local_library
+                .map(|library| (library, None))
+                .map_err(|_| download_err),
+        }
+    }
+
+    /// Serializes the library into the encoded `sfxlibrary.dat` format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let string = parse::write_sfx_library_to_string(self);
+        Ok(files::encoding::encode(string.as_bytes())?.into_bytes())
     }
 
-    fn should_try_update(library: Option<&SfxLibrary>) -> bool {
+    fn should_try_update(library: Option<&SfxLibrary>, source: &dyn ContentSource) -> bool {
         const SFX_VERSION_ENDPOINT: &str = "sfxlibrary_version.txt";
 
         let Some(library) = library else { return true };
 
-        requests::request_sfx_file(SFX_VERSION_ENDPOINT).ok()
-            .and_then(|response| response.text().ok())
+        source.fetch_sfx_file(SFX_VERSION_ENDPOINT).ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
             .map(|version| version != library.get_version())
             .unwrap_or(false) // request failed, don't bother updating
     }
@@ -98,6 +156,11 @@ impl SfxLibrary {
     pub fn credits(&self) -> &Vec<Credit> {
         &self.credits
     }
+
+    /// Number of records which couldn't be parsed and were left out.
+    pub fn dropped_records(&self) -> usize {
+        self.warnings.iter().filter(|warning| warning.is_dropped_record()).count()
+    }
 }
 
 impl SfxLibraryEntry {
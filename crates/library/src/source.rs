@@ -0,0 +1,150 @@
+use std::{fs, path::PathBuf};
+
+use once_cell::sync::OnceCell;
+use reqwest::{blocking::Client, header::*};
+use url::Url;
+
+use crate::{LibraryError, Result};
+
+const SFX_DIRECTORY: &str = "sfx/";
+const MUSIC_DIRECTORY: &str = "music/";
+
+/// Somewhere library files and sound files can be fetched from.
+///
+/// Paths are relative to the `sfx/` or `music/` directory of the source,
+/// e.g. `sfxlibrary.dat` or `s4451.ogg`.
+pub trait ContentSource: Send + Sync {
+    fn fetch_sfx_file(&self, path: &str) -> Result<Vec<u8>>;
+    fn fetch_music_file(&self, path: &str) -> Result<Vec<u8>>;
+
+    /// Offline sources are never asked for updates, so libraries are only loaded from local files.
+    fn is_offline(&self) -> bool { false }
+}
+
+/// Fetches files over HTTP from the official CDN or from a mirror with the same layout.
+pub struct HttpSource {
+    client: Client,
+    base_url: OnceCell<Url>,
+}
+
+impl Default for HttpSource {
+    fn default() -> Self {
+        Self::official()
+    }
+}
+
+impl HttpSource {
+    /// Uses the CDN reported by the Geometry Dash servers, which is looked up on first use.
+    pub fn official() -> Self {
+        Self {
+            client: Client::default(),
+            base_url: OnceCell::new(),
+        }
+    }
+
+    /// Uses a mirror which serves the `sfx/` and `music/` directories under `base_url`.
+    pub fn with_base_url(base_url: &str) -> Result<Self, url::ParseError> {
+        let mut base_url = base_url.trim().to_string();
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+
+        let base_url = Url::parse(&base_url)?;
+
+        Ok(Self {
+            client: Client::default(),
+            base_url: OnceCell::with_value(base_url),
+        })
+    }
+
+    fn base_url(&self) -> &Url {
+        self.base_url.get_or_init(|| self.get_cdn_url())
+    }
+
+    fn get_cdn_url(&self) -> Url {
+        const CDN_URL_REQUEST_URL: &str = "https://www.boomlings.com/database/getCustomContentURL.php";
+        const FALLBACK_CDN_URL: &str = "https://geometrydashfiles.b-cdn.net";
+
+        self.client
+            .post(CDN_URL_REQUEST_URL)
+            .header(USER_AGENT, "")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .send().ok()
+            .filter(|response| response.status().is_success())
+            .and_then(|response| response.text().ok())
+            .and_then(|url| Url::parse(&format!("{}/", url.trim_end_matches('/'))).ok())
+            .unwrap_or_else(|| Url::parse(&format!("{FALLBACK_CDN_URL}/")).unwrap())
+    }
+
+    fn fetch(&self, directory: &str, path: &str) -> Result<Vec<u8>> {
+        let url = self.base_url()
+            .join(directory)
+            .and_then(|url| url.join(path))
+            .expect("relative file paths should always be valid");
+
+        let network_error = |source| LibraryError::Network { url: url.to_string(), source };
+
+        let response = self.client
+            .get(url.as_str())
+            .send()
+            .map_err(network_error)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(LibraryError::HttpStatus { url: url.to_string(), status: status.as_u16() })
+        }
+
+        let bytes = response.bytes().map_err(network_error)?;
+
+        Ok(bytes.to_vec())
+    }
+}
+
+impl ContentSource for HttpSource {
+    fn fetch_sfx_file(&self, path: &str) -> Result<Vec<u8>> {
+        self.fetch(SFX_DIRECTORY, path)
+    }
+
+    fn fetch_music_file(&self, path: &str) -> Result<Vec<u8>> {
+        self.fetch(MUSIC_DIRECTORY, path)
+    }
+}
+
+/// Reads files from a local directory laid out like the CDN,
+/// i.e. containing `sfx/` and `music/` subdirectories.
+pub struct LocalSource {
+    root: PathBuf,
+}
+
+impl LocalSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl ContentSource for LocalSource {
+    fn fetch_sfx_file(&self, path: &str) -> Result<Vec<u8>> {
+        let path = self.root.join(SFX_DIRECTORY).join(path);
+        fs::read(&path).map_err(LibraryError::io(path))
+    }
+
+    fn fetch_music_file(&self, path: &str) -> Result<Vec<u8>> {
+        let path = self.root.join(MUSIC_DIRECTORY).join(path);
+        fs::read(&path).map_err(LibraryError::io(path))
+    }
+}
+
+/// Never touches the network; every fetch fails.
+pub struct OfflineSource;
+
+impl ContentSource for OfflineSource {
+    fn fetch_sfx_file(&self, path: &str) -> Result<Vec<u8>> {
+        Err(LibraryError::Offline { path: path.to_string() })
+    }
+
+    fn fetch_music_file(&self, path: &str) -> Result<Vec<u8>> {
+        Err(LibraryError::Offline { path: path.to_string() })
+    }
+
+    fn is_offline(&self) -> bool { true }
+}
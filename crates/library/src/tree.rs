@@ -0,0 +1,200 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use ahash::HashSet;
+
+use crate::*;
+use crate::sfx::{EntryKind, SfxLibraryEntry};
+
+/// Separates category names in [`SfxLibrary::path_string`].
+pub const PATH_SEPARATOR: &str = "/";
+
+/// Sums over all sounds within a category, including nested categories.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CategoryTotals {
+    pub bytes: BytesSize,
+    pub duration: Duration,
+    pub sounds: usize,
+    pub categories: usize,
+}
+
+impl SfxLibrary {
+    pub fn get_entry(&self, id: EntryId) -> Option<&SfxLibraryEntry> {
+        self.entries.get(&id)
+    }
+
+    /// `None` for the root and for entries whose parent isn't part of the library.
+    pub fn parent_of(&self, id: EntryId) -> Option<&SfxLibraryEntry> {
+        if id == self.root_id { return None }
+
+        self.entries.get(&id)
+            .and_then(|entry| self.entries.get(&entry.parent_id))
+    }
+
+    /// The parent, grandparent etc. of an entry, ending with the root if it's reachable.
+    pub fn ancestors(&self, id: EntryId) -> Ancestors<'_> {
+        Ancestors {
+            library: self,
+            next: self.parent_of(id),
+            visited: [id].into_iter().collect(),
+        }
+    }
+
+    /// Names of the categories containing an entry and the entry itself, excluding the root,
+    /// e.g. `Sound Effects/Explosions/Fire In The Hole`. Empty for the root.
+    pub fn path_string(&self, id: EntryId) -> String {
+        let Some(entry) = self.entries.get(&id).filter(|entry| entry.id != self.root_id) else {
+            return String::new()
+        };
+
+        let mut names: Vec<&str> = self.ancestors(id)
+            .filter(|ancestor| ancestor.id != self.root_id)
+            .map(|ancestor| ancestor.name.as_str())
+            .collect();
+        names.reverse();
+        names.push(&entry.name);
+
+        names.join(PATH_SEPARATOR)
+    }
+
+    /// Pre-order traversal starting at (and including) `entry`, keeping the order of children.
+    pub fn iter_depth_first<'a>(&'a self, entry: &'a SfxLibraryEntry) -> DepthFirst<'a> {
+        DepthFirst {
+            library: self,
+            stack: vec![entry],
+            visited: HashSet::default(),
+        }
+    }
+
+    /// Level-order traversal starting at (and including) `entry`.
+    pub fn iter_breadth_first<'a>(&'a self, entry: &'a SfxLibraryEntry) -> BreadthFirst<'a> {
+        BreadthFirst {
+            library: self,
+            queue: VecDeque::from([entry]),
+            visited: HashSet::default(),
+        }
+    }
+
+    /// All entries within a category in depth-first order, excluding the category itself.
+    pub fn descendants<'a>(&'a self, category: &'a SfxLibraryEntry) -> impl Iterator<Item = &'a SfxLibraryEntry> {
+        self.iter_depth_first(category).skip(1)
+    }
+
+    pub fn category_totals(&self, category: &SfxLibraryEntry) -> CategoryTotals {
+        self.descendants(category)
+            .fold(CategoryTotals::default(), |mut totals, entry| {
+                match entry.kind {
+                    EntryKind::Category => totals.categories += 1,
+                    EntryKind::Sound { bytes, duration } => {
+                        totals.bytes += bytes;
+                        totals.duration += duration;
+                        totals.sounds += 1;
+                    }
+                }
+                totals
+            })
+    }
+}
+
+pub struct Ancestors<'a> {
+    library: &'a SfxLibrary,
+    next: Option<&'a SfxLibraryEntry>,
+    visited: HashSet<EntryId>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = &'a SfxLibraryEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // stop at parent cycles instead of looping forever
+        let entry = self.next.take().filter(|entry| self.visited.insert(entry.id))?;
+        self.next = self.library.parent_of(entry.id);
+        Some(entry)
+    }
+}
+
+pub struct DepthFirst<'a> {
+    library: &'a SfxLibrary,
+    stack: Vec<&'a SfxLibraryEntry>,
+    visited: HashSet<EntryId>,
+}
+
+impl<'a> Iterator for DepthFirst<'a> {
+    type Item = &'a SfxLibraryEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = self.stack.pop()?;
+            if !self.visited.insert(entry.id) { continue }
+
+            let children = self.library.iter_children(entry).collect::<Vec<_>>();
+            self.stack.extend(children.into_iter().rev());
+            return Some(entry)
+        }
+    }
+}
+
+pub struct BreadthFirst<'a> {
+    library: &'a SfxLibrary,
+    queue: VecDeque<&'a SfxLibraryEntry>,
+    visited: HashSet<EntryId>,
+}
+
+impl<'a> Iterator for BreadthFirst<'a> {
+    type Item = &'a SfxLibraryEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = self.queue.pop_front()?;
+            if !self.visited.insert(entry.id) { continue }
+
+            self.queue.extend(self.library.iter_children(entry));
+            return Some(entry)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sfx_library() -> SfxLibrary {
+        parse::sfx_library_from_str("1,v1,1,0,0,0;2,Sound Effects,1,1,0,0;3,Explosions,1,2,0,0;\
+            10,Boom,0,3,100,150;11,Bang,0,3,200,50;12,Click,0,2,50,10;4,Empty,1,1,0,0")
+    }
+
+    fn ids<'a>(entries: impl Iterator<Item = &'a SfxLibraryEntry>) -> Vec<EntryId> {
+        entries.map(|entry| entry.id).collect()
+    }
+
+    #[test]
+    fn test_navigation() {
+        let library = sfx_library();
+
+        assert_eq!(library.parent_of(10).map(|entry| entry.id), Some(3));
+        assert_eq!(library.parent_of(1), None);
+        assert_eq!(ids(library.ancestors(10)), vec![3, 2, 1]);
+
+        assert_eq!(library.path_string(10), "Sound Effects/Explosions/Boom");
+        assert_eq!(library.path_string(2), "Sound Effects");
+        assert_eq!(library.path_string(1), "");
+
+        let root = library.get_root();
+        assert_eq!(ids(library.iter_depth_first(root)), vec![1, 2, 3, 10, 11, 12, 4]);
+        assert_eq!(ids(library.iter_breadth_first(root)), vec![1, 2, 4, 3, 12, 10, 11]);
+        assert_eq!(ids(library.descendants(&library.entries[&2])), vec![3, 10, 11, 12]);
+    }
+
+    #[test]
+    fn test_category_totals() {
+        let library = sfx_library();
+
+        assert_eq!(library.category_totals(&library.entries[&2]), CategoryTotals {
+            bytes: 350,
+            duration: Duration::from_millis(2100),
+            sounds: 3,
+            categories: 1,
+        });
+        assert_eq!(library.category_totals(&library.entries[&4]), CategoryTotals::default());
+    }
+}
@@ -0,0 +1,178 @@
+use ahash::{HashMap, HashSet};
+use itertools::Itertools;
+
+use crate::*;
+use crate::sfx::EntryKind;
+
+/// Structural problems of the SFX library tree. Entries are listed by ID in ascending order.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ValidationReport {
+    /// Entries other than the root whose parent isn't part of the library.
+    pub orphans: Vec<EntryId>,
+    /// Entries whose parent is a sound instead of a category.
+    pub sound_parents: Vec<EntryId>,
+    /// Each cycle of parent references once, starting at its lowest ID and following the parents.
+    pub cycles: Vec<Vec<EntryId>>,
+    /// IDs of records which were left out because an earlier record had the same ID.
+    pub duplicate_ids: Vec<EntryId>,
+    /// Entries which can't be reached from the root, e.g. because an ancestor is orphaned or part of a cycle.
+    pub unreachable: Vec<EntryId>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.orphans.is_empty()
+            && self.sound_parents.is_empty()
+            && self.cycles.is_empty()
+            && self.duplicate_ids.is_empty()
+            && self.unreachable.is_empty()
+    }
+
+    /// Total number of problems found.
+    pub fn problem_count(&self) -> usize {
+        self.orphans.len()
+            + self.sound_parents.len()
+            + self.cycles.len()
+            + self.duplicate_ids.len()
+            + self.unreachable.len()
+    }
+}
+
+impl SfxLibrary {
+    /// Checks the tree for problems which the library works around when it's loaded.
+    pub fn validate(&self) -> ValidationReport {
+        let sorted_entries = self.entries.values()
+            .sorted_by_key(|entry| entry.id)
+            .collect_vec();
+
+        let orphans = sorted_entries.iter()
+            .filter(|entry| entry.id != self.root_id && !self.entries.contains_key(&entry.parent_id))
+            .map(|entry| entry.id)
+            .collect();
+
+        let sound_parents = sorted_entries.iter()
+            .filter(|entry| entry.id != self.root_id)
+            .filter(|entry| self.entries.get(&entry.parent_id)
+                .is_some_and(|parent| matches!(parent.kind, EntryKind::Sound { .. })))
+            .map(|entry| entry.id)
+            .collect();
+
+        let duplicate_ids = self.warnings.iter()
+            .filter_map(|warning| match warning {
+                LibraryWarning::DuplicateId { id, .. } => Some(*id),
+                _ => None,
+            })
+            .sorted()
+            .dedup()
+            .collect();
+
+        let mut reachable = HashSet::default();
+        let mut stack = vec![self.root_id];
+        while let Some(id) = stack.pop() {
+            if !reachable.insert(id) { continue }
+            stack.extend(self.child_map.get(&id).into_iter().flatten());
+        }
+
+        let unreachable = sorted_entries.iter()
+            .map(|entry| entry.id)
+            .filter(|id| !reachable.contains(id))
+            .collect();
+
+        ValidationReport {
+            orphans,
+            sound_parents,
+            cycles: self.find_cycles(),
+            duplicate_ids,
+            unreachable,
+        }
+    }
+
+    fn find_cycles(&self) -> Vec<Vec<EntryId>> {
+        // each entry has a single parent, so following parents from any entry either ends or loops
+        let mut finished = HashSet::default();
+        let mut cycles = Vec::new();
+
+        for &start in self.entries.keys().sorted() {
+            let mut path = Vec::new();
+            let mut position: HashMap<EntryId, usize> = HashMap::default();
+            let mut current = start;
+
+            loop {
+                if finished.contains(&current) { break }
+                if let Some(&index) = position.get(&current) {
+                    let cycle = &path[index..];
+                    let lowest = cycle.iter().position_min().expect("cycles aren't empty");
+                    cycles.push(cycle[lowest..].iter().chain(&cycle[..lowest]).copied().collect());
+                    break
+                }
+                let Some(entry) = self.entries.get(&current) else { break };
+
+                position.insert(current, path.len());
+                path.push(current);
+                current = entry.parent_id;
+            }
+
+            finished.extend(path);
+        }
+
+        cycles.sort();
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_valid_library() {
+        let library = parse::sfx_library_from_str("1,v1,1,0,0,0;2,Explosions,1,1,0,0;10,Boom,0,2,100,10");
+
+        assert_eq!(library.root_id, 1);
+        assert!(library.validate().is_valid());
+    }
+
+    #[test]
+    fn test_root_is_computed() {
+        // the root isn't the first entry
+        let library = parse::sfx_library_from_str("10,Boom,0,2,100,10;2,Explosions,1,1,0,0;1,v1,1,0,0,0");
+
+        assert_eq!(library.root_id, 1);
+        assert_eq!(library.get_version(), "v1");
+        assert!(library.validate().is_valid());
+    }
+
+    #[test]
+    fn test_first_entry_is_root() {
+        // another category without a parent comes before the root's children
+        let library = parse::sfx_library_from_str("1,v1,1,0,0,0;5,Stray,1,99,0,0;2,Explosions,1,1,0,0;10,Boom,0,2,100,10");
+
+        assert_eq!(library.root_id, 1);
+        assert_eq!(library.get_version(), "v1");
+
+        let report = library.validate();
+        assert_eq!(report.orphans, vec![5]);
+        assert_eq!(report.unreachable, vec![5]);
+    }
+
+    #[test]
+    fn test_invalid_library() {
+        let library = parse::sfx_library_from_str(
+            "1,v1,1,0,0,0;2,Explosions,1,1,0,0;10,Boom,0,2,100,10;\
+            2,Duplicate,1,1,0,0;\
+            11,Orphan,0,99,100,10;\
+            20,Loop A,1,21,0,0;21,Loop B,1,20,0,0;22,In Loop,0,20,100,10;\
+            30,Child Of Sound,0,10,100,10"
+        );
+
+        let report = library.validate();
+        assert_eq!(report, ValidationReport {
+            orphans: vec![11],
+            sound_parents: vec![30],
+            cycles: vec![vec![20, 21]],
+            duplicate_ids: vec![2],
+            unreachable: vec![11, 20, 21, 22],
+        });
+        assert_eq!(library.entries[&2].name, "Explosions");
+    }
+}